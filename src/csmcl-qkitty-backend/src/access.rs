@@ -0,0 +1,311 @@
+// Role-based access control
+//
+// Roles form a ladder: Controller > Admin > Curator > User. Controllers are the
+// canister's IC controllers and are never stored; every other principal is a User
+// unless it has been granted Admin or Curator. A principal may only grant or
+// revoke roles strictly below its own.
+use candid::{CandidType, Principal};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::error::KittyError;
+
+/// Length of the rate limiting window in nanoseconds (1 minute)
+const RATE_LIMIT_WINDOW_NS: u64 = 60_000_000_000;
+
+/// Maximum number of writes a principal may make per window
+const RATE_LIMIT_MAX_WRITES: u32 = 20;
+
+/// Maximum number of wisdom requests per window whose keywords a principal may record
+const RATE_LIMIT_MAX_KEYWORD_RECORDINGS: u32 = 20;
+
+thread_local! {
+    static ROLE_ASSIGNMENTS: RefCell<HashMap<Principal, Role>> = RefCell::new(HashMap::new());
+    // Rate limiters are not persisted; they reset on upgrade
+    static WRITE_LIMITER: RefCell<RateLimiter> = RefCell::new(RateLimiter::new(RATE_LIMIT_MAX_WRITES));
+    static KEYWORD_LIMITER: RefCell<RateLimiter> = RefCell::new(RateLimiter::new(RATE_LIMIT_MAX_KEYWORD_RECORDINGS));
+}
+
+/// Role determines which endpoints a principal may call
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Controller, // IC controllers of the canister (implicit, can't be granted)
+    Admin,      // Manages curators
+    Curator,    // Manages wisdom templates, adjectives and phrases
+    User,       // Everyone else
+}
+
+impl Role {
+    /// Position on the role ladder; higher outranks lower
+    fn rank(self) -> u8 {
+        match self {
+            Role::Controller => 3,
+            Role::Admin => 2,
+            Role::Curator => 1,
+            Role::User => 0,
+        }
+    }
+
+    /// True if this role grants at least the permissions of `required`
+    pub fn includes(self, required: Role) -> bool {
+        self.rank() >= required.rank()
+    }
+}
+
+/// RateLimiter counts actions per principal in fixed one-minute windows
+struct RateLimiter {
+    max_per_window: u32,
+    windows: HashMap<Principal, (u64, u32)>, // (window start, actions in window)
+}
+
+impl RateLimiter {
+    fn new(max_per_window: u32) -> Self {
+        Self { max_per_window, windows: HashMap::new() }
+    }
+
+    /// Count an action by `principal` at `now`; false if its window's budget is spent
+    /// Expired windows are dropped first, so principals that stop calling don't accumulate.
+    fn charge(&mut self, principal: &Principal, now: u64) -> bool {
+        self.windows.retain(|_, (window_start, _)| now.saturating_sub(*window_start) < RATE_LIMIT_WINDOW_NS);
+        let (_, count) = self.windows.entry(*principal).or_insert((now, 0));
+        if *count >= self.max_per_window {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+/// RoleAssignment pairs a principal with an explicitly granted role
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RoleAssignment {
+    principal: Principal,
+    role: Role,
+}
+
+/// The effective role of a principal
+pub fn role_of(principal: &Principal) -> Role {
+    if ic_cdk::api::is_controller(principal) {
+        return Role::Controller;
+    }
+    assigned_role(principal)
+}
+
+/// The explicitly granted role of a principal (User if none)
+fn assigned_role(principal: &Principal) -> Role {
+    ROLE_ASSIGNMENTS.with(|r| r.borrow().get(principal).copied().unwrap_or(Role::User))
+}
+
+/// Guard: the caller's principal, unless it is anonymous
+/// Every per-user endpoint goes through this so logged-out visitors never share one record
+pub fn authenticated_caller() -> Result<Principal, KittyError> {
+    authenticate(ic_cdk::api::caller())
+}
+
+/// `caller`, unless it is anonymous
+fn authenticate(caller: Principal) -> Result<Principal, KittyError> {
+    if caller == Principal::anonymous() {
+        return Err(KittyError::Unauthenticated(
+            "Please log in with Internet Identity to bond with your kitty".to_string(),
+        ));
+    }
+    Ok(caller)
+}
+
+/// Guard: succeed only if the caller holds at least the `required` role
+pub fn require_role(required: Role) -> Result<(), KittyError> {
+    check_role(role_of(&ic_cdk::api::caller()), required)
+}
+
+/// Succeed only if `role` is at least `required`
+fn check_role(role: Role, required: Role) -> Result<(), KittyError> {
+    if role.includes(required) {
+        Ok(())
+    } else {
+        Err(KittyError::Unauthorized(format!(
+            "This action requires the {:?} role (caller has {:?})",
+            required, role
+        )))
+    }
+}
+
+/// Guard: count a write by `principal`, failing once it exceeds the per-minute budget
+/// Controllers are exempt so deploy scripts can seed content in bulk
+pub fn check_rate_limit(principal: &Principal) -> Result<(), KittyError> {
+    if ic_cdk::api::is_controller(principal) {
+        return Ok(());
+    }
+    if !WRITE_LIMITER.with(|l| l.borrow_mut().charge(principal, ic_cdk::api::time())) {
+        return Err(KittyError::RateLimited(format!(
+            "At most {} changes per minute are allowed; please wait a moment",
+            RATE_LIMIT_MAX_WRITES
+        )));
+    }
+    Ok(())
+}
+
+/// Count a keyword recording by `principal`; false once it exceeds the per-minute budget
+/// Kept apart from the write budget, so generating wisdom never blocks real changes
+pub fn allow_keyword_recording(principal: &Principal) -> bool {
+    ic_cdk::api::is_controller(principal)
+        || KEYWORD_LIMITER.with(|l| l.borrow_mut().charge(principal, ic_cdk::api::time()))
+}
+
+/// Grant `role` to `principal` on behalf of the caller
+pub fn grant(principal: Principal, role: Role) -> Result<(), KittyError> {
+    grant_as(role_of(&ic_cdk::api::caller()), role_of(&principal), principal, role)
+}
+
+/// Grant `role` to `principal`, currently `target_role`, on behalf of a `caller_role`
+fn grant_as(caller_role: Role, target_role: Role, principal: Principal, role: Role) -> Result<(), KittyError> {
+    match role {
+        Role::Controller => {
+            return Err(KittyError::InvalidInput(
+                "The Controller role comes from the canister settings and can't be granted".to_string(),
+            ))
+        }
+        Role::User => return revoke_as(caller_role, target_role, principal),
+        Role::Admin | Role::Curator => {}
+    }
+    if principal == Principal::anonymous() {
+        return Err(KittyError::InvalidInput("Roles can't be granted to the anonymous principal".to_string()));
+    }
+
+    if caller_role.rank() <= role.rank() {
+        return Err(KittyError::Unauthorized(format!(
+            "Granting {:?} requires a higher role (caller has {:?})",
+            role, caller_role
+        )));
+    }
+    ensure_outranks(caller_role, target_role)?;
+
+    ROLE_ASSIGNMENTS.with(|r| r.borrow_mut().insert(principal, role));
+    Ok(())
+}
+
+/// Revoke any explicitly granted role from `principal` on behalf of the caller
+pub fn revoke(principal: Principal) -> Result<(), KittyError> {
+    revoke_as(role_of(&ic_cdk::api::caller()), role_of(&principal), principal)
+}
+
+/// Revoke the role of `principal`, currently `target_role`, on behalf of a `caller_role`
+fn revoke_as(caller_role: Role, target_role: Role, principal: Principal) -> Result<(), KittyError> {
+    ensure_outranks(caller_role, target_role)?;
+
+    ROLE_ASSIGNMENTS.with(|r| r.borrow_mut().remove(&principal));
+    Ok(())
+}
+
+/// The caller may only change principals whose current role is below its own
+fn ensure_outranks(caller_role: Role, target_role: Role) -> Result<(), KittyError> {
+    if caller_role.rank() <= target_role.rank() {
+        return Err(KittyError::Unauthorized(format!(
+            "Changing a principal with the {:?} role requires a higher role (caller has {:?})",
+            target_role, caller_role
+        )));
+    }
+    Ok(())
+}
+
+/// All explicitly granted roles
+pub fn assignments() -> Vec<RoleAssignment> {
+    ROLE_ASSIGNMENTS.with(|r| {
+        r.borrow()
+            .iter()
+            .map(|(principal, role)| RoleAssignment { principal: *principal, role: *role })
+            .collect()
+    })
+}
+
+/// Get a copy of the role assignments (used for persistence)
+pub fn snapshot() -> HashMap<Principal, Role> {
+    ROLE_ASSIGNMENTS.with(|r| r.borrow().clone())
+}
+
+/// Replace the role assignments (used when restoring after an upgrade)
+pub fn restore(roles: HashMap<Principal, Role>) {
+    ROLE_ASSIGNMENTS.with(|r| *r.borrow_mut() = roles);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    #[test]
+    fn the_role_ladder_is_ordered() {
+        let ladder = [Role::User, Role::Curator, Role::Admin, Role::Controller];
+        for (i, &role) in ladder.iter().enumerate() {
+            for (j, &required) in ladder.iter().enumerate() {
+                assert_eq!(role.includes(required), i >= j, "{:?} includes {:?}", role, required);
+                assert_eq!(check_role(role, required).is_ok(), i >= j);
+            }
+        }
+        assert!(matches!(check_role(Role::Curator, Role::Admin), Err(KittyError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn anonymous_callers_are_not_authenticated() {
+        assert!(matches!(authenticate(Principal::anonymous()), Err(KittyError::Unauthenticated(_))));
+        assert_eq!(authenticate(principal(1)), Ok(principal(1)));
+    }
+
+    #[test]
+    fn roles_are_granted_only_below_the_caller() {
+        grant_as(Role::Controller, Role::User, principal(1), Role::Admin).unwrap();
+        grant_as(Role::Admin, Role::User, principal(2), Role::Curator).unwrap();
+        assert_eq!(assigned_role(&principal(1)), Role::Admin);
+        assert_eq!(assigned_role(&principal(2)), Role::Curator);
+
+        let peer = grant_as(Role::Admin, Role::User, principal(3), Role::Admin);
+        assert!(matches!(peer, Err(KittyError::Unauthorized(_))));
+        let controller = grant_as(Role::Controller, Role::User, principal(3), Role::Controller);
+        assert!(matches!(controller, Err(KittyError::InvalidInput(_))));
+        let anonymous = grant_as(Role::Controller, Role::User, Principal::anonymous(), Role::Curator);
+        assert!(matches!(anonymous, Err(KittyError::InvalidInput(_))));
+        assert_eq!(assignments().len(), 2);
+    }
+
+    #[test]
+    fn roles_are_revoked_only_below_the_caller() {
+        grant_as(Role::Controller, Role::User, principal(1), Role::Admin).unwrap();
+        grant_as(Role::Controller, Role::User, principal(2), Role::Curator).unwrap();
+
+        // An admin can't demote another admin or a controller
+        assert!(matches!(revoke_as(Role::Admin, Role::Admin, principal(1)), Err(KittyError::Unauthorized(_))));
+        assert!(matches!(revoke_as(Role::Admin, Role::Controller, principal(9)), Err(KittyError::Unauthorized(_))));
+        // nor can a controller demote a controller, which would have to be done in the canister settings
+        assert!(matches!(revoke_as(Role::Controller, Role::Controller, principal(9)), Err(KittyError::Unauthorized(_))));
+
+        revoke_as(Role::Admin, Role::Curator, principal(2)).unwrap();
+        assert_eq!(assigned_role(&principal(2)), Role::User);
+        // Granting User is a revoke
+        grant_as(Role::Controller, Role::Admin, principal(1), Role::User).unwrap();
+        assert!(assignments().is_empty());
+    }
+
+    #[test]
+    fn rate_limits_count_actions_per_window() {
+        let mut limiter = RateLimiter::new(2);
+        assert!(limiter.charge(&principal(1), 0));
+        assert!(limiter.charge(&principal(1), 1));
+        assert!(!limiter.charge(&principal(1), 2));
+        assert!(limiter.charge(&principal(2), 2));
+        assert!(limiter.charge(&principal(1), RATE_LIMIT_WINDOW_NS));
+    }
+
+    #[test]
+    fn rate_limits_forget_expired_windows() {
+        let mut limiter = RateLimiter::new(2);
+        for id in 1..=5 {
+            limiter.charge(&principal(id), u64::from(id));
+        }
+        assert_eq!(limiter.windows.len(), 5);
+        limiter.charge(&principal(9), 3 + RATE_LIMIT_WINDOW_NS);
+        assert_eq!(limiter.windows.len(), 3);
+    }
+}
@@ -0,0 +1,203 @@
+// Calendar facts for date-aware templates
+//
+// IC `time()` is nanoseconds since the Unix epoch in UTC. A `Moment` converts it to
+// the local calendar of the caller, given their UTC offset in minutes (the canister
+// has no time zone database, so the frontend sends the offset it observes).
+//
+// Seasons are meteorological and follow the northern hemisphere:
+// December-February is winter, March-May spring, June-August summer and
+// September-November autumn.
+use crate::error::KittyError;
+
+/// Most negative UTC offset in use (UTC-12:00), in minutes
+pub const MIN_UTC_OFFSET_MINUTES: i16 = -12 * 60;
+
+/// Most positive UTC offset in use (UTC+14:00), in minutes
+pub const MAX_UTC_OFFSET_MINUTES: i16 = 14 * 60;
+
+pub const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+pub const SEASONS: [&str; 4] = ["winter", "spring", "summer", "autumn"];
+
+pub const TIMES_OF_DAY: [&str; 4] = ["morning", "afternoon", "evening", "night"];
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+const NANOS_PER_MINUTE: i64 = 60_000_000_000;
+
+/// Moment is a point in time on the caller's local calendar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Moment {
+    year: i64,
+    month: u32,   // 1-12
+    day: u32,     // 1-31
+    weekday: u32, // 0 = Monday .. 6 = Sunday
+    hour: u32,    // 0-23
+}
+
+impl Moment {
+    /// The local calendar moment for IC time `now_ns` at `utc_offset_minutes`
+    pub fn at(now_ns: u64, utc_offset_minutes: i16) -> Self {
+        let local_minutes = (now_ns as i64) / NANOS_PER_MINUTE + i64::from(utc_offset_minutes);
+        let days = local_minutes.div_euclid(24 * 60);
+        let minute_of_day = local_minutes.rem_euclid(24 * 60);
+        let (year, month, day) = civil_from_days(days);
+
+        Self {
+            year,
+            month,
+            day,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 3).rem_euclid(7) as u32,
+            hour: (minute_of_day / 60) as u32,
+        }
+    }
+
+    /// "Monday"
+    pub fn weekday(&self) -> &'static str {
+        WEEKDAYS[self.weekday as usize]
+    }
+
+    /// "October"
+    pub fn month(&self) -> &'static str {
+        MONTHS[self.month as usize - 1]
+    }
+
+    /// Day of the month, 1-31
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    pub fn year(&self) -> i64 {
+        self.year
+    }
+
+    /// "October 18, 2026"
+    pub fn date(&self) -> String {
+        format!("{} {}, {}", self.month(), self.day, self.year)
+    }
+
+    /// "winter", "spring", "summer" or "autumn"
+    pub fn season(&self) -> &'static str {
+        SEASONS[(self.month as usize % 12) / 3]
+    }
+
+    /// "morning" (5-11h), "afternoon" (12-16h), "evening" (17-20h) or "night"
+    pub fn time_of_day(&self) -> &'static str {
+        match self.hour {
+            5..=11 => "morning",
+            12..=16 => "afternoon",
+            17..=20 => "evening",
+            _ => "night",
+        }
+    }
+}
+
+/// Check a caller-supplied UTC offset
+pub fn validate_utc_offset(minutes: i16) -> Result<i16, KittyError> {
+    if !(MIN_UTC_OFFSET_MINUTES..=MAX_UTC_OFFSET_MINUTES).contains(&minutes) {
+        return Err(KittyError::InvalidInput(format!(
+            "utc_offset_minutes must be between {} and {}",
+            MIN_UTC_OFFSET_MINUTES, MAX_UTC_OFFSET_MINUTES
+        )));
+    }
+    Ok(minutes)
+}
+
+/// Year, month and day for a count of days since 1970-01-01 (proleptic Gregorian)
+/// From Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // March = 0
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+    #[test]
+    fn the_epoch_is_a_thursday() {
+        let moment = Moment::at(0, 0);
+        assert_eq!(moment.date(), "January 1, 1970");
+        assert_eq!(moment.weekday(), "Thursday");
+        assert_eq!(moment.season(), "winter");
+        assert_eq!(moment.time_of_day(), "night");
+    }
+
+    #[test]
+    fn known_dates() {
+        // (Unix seconds, weekday, date)
+        for (secs, weekday, date) in [
+            (951_782_400, "Tuesday", "February 29, 2000"),
+            (1_735_687_800, "Tuesday", "December 31, 2024"),
+            (1_792_315_800, "Sunday", "October 18, 2026"),
+            (4_107_517_200, "Sunday", "February 28, 2100"),
+        ] {
+            let moment = Moment::at(secs * NANOS_PER_SEC, 0);
+            assert_eq!((moment.weekday(), moment.date().as_str()), (weekday, date), "{}", secs);
+        }
+
+        let moment = Moment::at(1_792_315_800 * NANOS_PER_SEC, 0);
+        assert_eq!((moment.day(), moment.month(), moment.year()), (18, "October", 2026));
+        assert_eq!((moment.season(), moment.time_of_day()), ("autumn", "morning"));
+    }
+
+    #[test]
+    fn utc_offsets_can_change_the_local_date() {
+        // 2024-12-31 23:30 UTC
+        let now = 1_735_687_800 * NANOS_PER_SEC;
+        assert_eq!(Moment::at(now, 60).date(), "January 1, 2025");
+        assert_eq!(Moment::at(now, 60).weekday(), "Wednesday");
+        assert_eq!(Moment::at(now, 29).date(), "December 31, 2024");
+        assert_eq!(Moment::at(now, MIN_UTC_OFFSET_MINUTES).date(), "December 31, 2024");
+
+        // Before the epoch in the caller's time zone
+        let moment = Moment::at(0, -60);
+        assert_eq!((moment.weekday(), moment.date().as_str()), ("Wednesday", "December 31, 1969"));
+        assert_eq!(moment.time_of_day(), "night");
+    }
+
+    #[test]
+    fn seasons_follow_the_meteorological_months() {
+        let seasons: Vec<&str> = (1..=12)
+            .map(|month| Moment { year: 2026, month, day: 1, weekday: 0, hour: 12 }.season())
+            .collect();
+        assert_eq!(
+            seasons,
+            [
+                "winter", "winter", "spring", "spring", "spring", "summer",
+                "summer", "summer", "autumn", "autumn", "autumn", "winter",
+            ]
+        );
+    }
+
+    #[test]
+    fn times_of_day_follow_the_hour() {
+        let times: Vec<&str> = [4, 5, 11, 12, 16, 17, 20, 21]
+            .into_iter()
+            .map(|hour| Moment { year: 2026, month: 1, day: 1, weekday: 0, hour }.time_of_day())
+            .collect();
+        assert_eq!(times, ["night", "morning", "morning", "afternoon", "afternoon", "evening", "evening", "night"]);
+    }
+
+    #[test]
+    fn utc_offsets_must_be_in_use_somewhere() {
+        assert_eq!(validate_utc_offset(MIN_UTC_OFFSET_MINUTES), Ok(-720));
+        assert_eq!(validate_utc_offset(MAX_UTC_OFFSET_MINUTES), Ok(840));
+        assert!(validate_utc_offset(-721).is_err());
+        assert!(validate_utc_offset(841).is_err());
+    }
+}
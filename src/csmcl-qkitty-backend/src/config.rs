@@ -0,0 +1,214 @@
+// Kitty configuration: state catalogue, mood catalogue, rotation periods, energy bounds
+// and the context fallback chain
+//
+// Supplied as the optional init/upgrade argument or through the controller-only
+// `set_kitty_config` endpoint, so the same wasm can run a "fast" demo kitty on a
+// local replica and a slow one in production.
+use candid::CandidType;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use crate::error::KittyError;
+use crate::validation;
+
+thread_local! {
+    static KITTY_CONFIG: RefCell<KittyConfig> = RefCell::new(KittyConfig::default());
+}
+
+/// Longest allowed context fallback chain, counting the requested context
+pub const MAX_FALLBACK_DEPTH: usize = 8;
+
+/// KittyConfig defines how the global kitty state rotates over time
+/// and where wisdom requests for a context without templates go instead
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct KittyConfig {
+    pub(crate) quantum_states: Vec<String>,                // Quantum states the kitty rotates through
+    pub(crate) zen_moods: Vec<String>,                     // Zen moods the kitty rotates through
+    pub(crate) state_period_secs: u64,                     // Seconds each quantum state lasts
+    pub(crate) mood_period_secs: u64,                      // Seconds each zen mood lasts
+    pub(crate) energy_period_secs: u64,                    // Seconds each energy level lasts
+    pub(crate) energy_min: u8,                             // Lowest energy level
+    pub(crate) energy_max: u8,                             // Highest energy level
+    pub(crate) default_context: String,                    // Last resort for every fallback chain
+    pub(crate) context_fallbacks: HashMap<String, String>, // Context -> context to try next
+}
+
+impl Default for KittyConfig {
+    fn default() -> Self {
+        Self {
+            quantum_states: vec![
+                "Superposition".to_string(), // Possibilities, choices
+                "Entangled".to_string(),     // Connections, relationships
+                "Coherent".to_string(),      // Clarity, focus
+                "Resonating".to_string(),    // Harmony, balance
+                "Folded".to_string(),        // Time, perspective
+            ],
+            zen_moods: vec![
+                "Tranquil".to_string(),      // Peaceful, calm
+                "Contemplative".to_string(), // Thoughtful, reflective
+                "Playful".to_string(),       // Curious, energetic
+                "Mysterious".to_string(),    // Enigmatic, deep
+                "Enlightened".to_string(),   // Wise, insightful
+            ],
+            state_period_secs: 4 * 3600, // Quantum states rotate every 4 hours
+            mood_period_secs: 6 * 3600,  // Zen moods rotate every 6 hours
+            energy_period_secs: 1800,    // Energy fluctuates every 30 minutes
+            energy_min: 1,
+            energy_max: 10,
+            default_context: "general".to_string(),
+            context_fallbacks: HashMap::new(),
+        }
+    }
+}
+
+impl KittyConfig {
+    /// Check the configuration is usable, reporting the first problem found
+    pub fn validate(&self) -> Result<(), KittyError> {
+        validate_catalogue("quantum_states", &self.quantum_states)?;
+        validate_catalogue("zen_moods", &self.zen_moods)?;
+
+        if self.state_period_secs == 0 || self.mood_period_secs == 0 || self.energy_period_secs == 0 {
+            return Err(invalid("Rotation periods must be greater than zero".to_string()));
+        }
+        if self.energy_min == 0 || self.energy_min > self.energy_max {
+            return Err(invalid(format!(
+                "Energy bounds must satisfy 1 <= energy_min <= energy_max (got {}..={})",
+                self.energy_min, self.energy_max
+            )));
+        }
+
+        self.validate_fallbacks()
+    }
+
+    /// Contexts to try for `context`, in order: the context itself, its configured
+    /// fallbacks, then the default context
+    pub fn fallback_chain(&self, context: &str) -> Vec<String> {
+        let mut chain = vec![context.to_string()];
+        let mut current = context;
+        while let Some(next) = self.context_fallbacks.get(current) {
+            // validate() rules out cycles, but a restored config is not validated
+            if chain.contains(next) || chain.len() >= MAX_FALLBACK_DEPTH {
+                break;
+            }
+            chain.push(next.clone());
+            current = next;
+        }
+        if !chain.contains(&self.default_context) {
+            chain.push(self.default_context.clone());
+        }
+        chain
+    }
+
+    /// Fallback contexts must be valid keys, and every chain must end without a cycle
+    fn validate_fallbacks(&self) -> Result<(), KittyError> {
+        validate_context("default_context", &self.default_context)?;
+        for (context, fallback) in &self.context_fallbacks {
+            validate_context("context_fallbacks", context)?;
+            validate_context("context_fallbacks", fallback)?;
+        }
+
+        for context in self.context_fallbacks.keys() {
+            let mut seen = vec![context.as_str()];
+            let mut current = context.as_str();
+            while let Some(next) = self.context_fallbacks.get(current) {
+                if seen.contains(&next.as_str()) {
+                    return Err(invalid(format!(
+                        "context_fallbacks form a cycle: {} -> {}",
+                        seen.join(" -> "),
+                        next
+                    )));
+                }
+                seen.push(next);
+                current = next;
+            }
+            if seen.len() > MAX_FALLBACK_DEPTH {
+                return Err(invalid(format!(
+                    "context_fallbacks chain from '{}' is longer than {} contexts",
+                    context, MAX_FALLBACK_DEPTH
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A context named in the configuration must already be a normalized context key
+fn validate_context(field: &str, context: &str) -> Result<(), KittyError> {
+    if validation::context_key(context)? != context {
+        return Err(invalid(format!("{} context '{}' must be lowercase", field, context)));
+    }
+    Ok(())
+}
+
+/// A catalogue must be non-empty with unique names that pass input validation as-is
+fn validate_catalogue(field: &str, names: &[String]) -> Result<(), KittyError> {
+    if names.is_empty() {
+        return Err(invalid(format!("{} must not be empty", field)));
+    }
+
+    let mut seen = HashSet::new();
+    for name in names {
+        let cleaned = validation::sanitize_text(field, name, validation::MAX_KEY_CHARS)?;
+        if &cleaned != name {
+            return Err(invalid(format!(
+                "{} name '{}' contains extra whitespace or control characters",
+                field, name
+            )));
+        }
+        if !seen.insert(name) {
+            return Err(invalid(format!("{} contains duplicate name '{}'", field, name)));
+        }
+    }
+
+    Ok(())
+}
+
+fn invalid(message: String) -> KittyError {
+    KittyError::InvalidInput(format!("Invalid kitty configuration: {}", message))
+}
+
+/// Get a copy of the current configuration
+pub fn current() -> KittyConfig {
+    KITTY_CONFIG.with(|c| c.borrow().clone())
+}
+
+/// Validate and install a new configuration
+pub fn apply(config: KittyConfig) -> Result<(), KittyError> {
+    config.validate()?;
+    KITTY_CONFIG.with(|c| *c.borrow_mut() = config);
+    Ok(())
+}
+
+/// Validate and install a persisted configuration (used when restoring after an upgrade)
+/// Fails rather than falling back to the default, so a bad snapshot aborts the upgrade
+/// instead of discarding the configured catalogue
+pub fn restore(config: KittyConfig) -> Result<(), KittyError> {
+    apply(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_keeps_a_valid_configuration() {
+        let config = KittyConfig { zen_moods: vec!["Sleepy".to_string()], ..KittyConfig::default() };
+        assert!(restore(config).is_ok());
+        assert_eq!(current().zen_moods, ["Sleepy"]);
+    }
+
+    #[test]
+    fn restore_rejects_an_invalid_configuration() {
+        let config = KittyConfig { zen_moods: vec!["Sleepy".to_string()], ..KittyConfig::default() };
+        restore(config).unwrap();
+
+        let empty = restore(KittyConfig { zen_moods: Vec::new(), ..KittyConfig::default() });
+        assert!(matches!(empty, Err(KittyError::InvalidInput(_))));
+        let stalled = restore(KittyConfig { state_period_secs: 0, ..KittyConfig::default() });
+        assert!(matches!(stalled, Err(KittyError::InvalidInput(_))));
+        // The configuration in place is left alone
+        assert_eq!(current().zen_moods, ["Sleepy"]);
+    }
+}
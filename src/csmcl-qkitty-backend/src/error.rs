@@ -0,0 +1,14 @@
+// Typed errors returned across the Candid API
+use candid::CandidType;
+use serde::Deserialize;
+
+/// KittyError tells callers why a request was refused
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum KittyError {
+    Unauthenticated(String), // The caller is anonymous but the action is per-user
+    Unauthorized(String),    // The caller lacks the role required for this action
+    InvalidInput(String),    // The arguments were rejected
+    NotFound(String),        // The requested context, state, mood or record doesn't exist
+    Duplicate(String),       // The content being added already exists
+    RateLimited(String),     // The caller is making changes too quickly
+}
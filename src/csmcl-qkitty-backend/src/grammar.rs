@@ -0,0 +1,215 @@
+// English clean-up for rendered wisdom
+//
+// Templates are written with any adjective or phrase in mind, so substitution can
+// leave "a infinite field", a lowercase word at the start of a sentence, doubled
+// spaces where an optional section was empty, or "harmony.." when a phrase that
+// already ends in a full stop is followed by one. `polish` fixes these after
+// rendering, in three passes:
+//   1. whitespace collapses to single spaces, spaces before punctuation are dropped
+//      and runs of punctuation collapse to the strongest mark ("..." is kept)
+//   2. a/an agrees with the sound of the following word (initialisms such as "FBI"
+//      are read letter by letter, so their article is left as written)
+//   3. the first letter of every sentence is capitalized; a sentence ends at ., ! or ?
+//      followed by whitespace, so "qkitty.app" and initialisms like "e.g." don't end one
+
+/// Word stems that start with a vowel letter but a consonant sound ("a unicorn")
+/// "uni" is spelled out per stem so "an uninspired" and "an unintended" keep their "an".
+const CONSONANT_SOUND_PREFIXES: [&str; 15] = [
+    "unic", "unif", "unio", "uniq", "unis", "unit", "univ", "use", "usu", "uti", "uto", "ure", "eu", "ewe", "ubiq",
+];
+
+/// Whole words that start with a vowel letter but a consonant sound ("a one-off", but "an onion")
+const CONSONANT_SOUND_WORDS: [&str; 4] = ["one", "ones", "oneself", "once"];
+
+/// Words that start with a silent h ("an hour")
+const VOWEL_SOUND_PREFIXES: [&str; 5] = ["hour", "honest", "honor", "honour", "heir"];
+
+/// Punctuation that never takes a space before it
+const CLOSING_PUNCTUATION: [char; 6] = ['.', ',', ';', ':', '!', '?'];
+
+/// Tidy rendered wisdom so it reads as natural English
+pub fn polish(text: &str) -> String {
+    capitalize_sentences(&fix_articles(&tidy_spacing(text)))
+}
+
+/// Collapse whitespace and punctuation runs
+fn tidy_spacing(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut run = String::new();
+    let mut pending_space = false;
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            pending_space = true;
+        } else if CLOSING_PUNCTUATION.contains(&c) {
+            // Spaces before punctuation, and between marks of one run, are dropped
+            pending_space = false;
+            run.push(c);
+        } else {
+            if !run.is_empty() {
+                out.push_str(strongest_mark(&run));
+                run.clear();
+            }
+            if pending_space && !out.is_empty() {
+                out.push(' ');
+            }
+            pending_space = false;
+            out.push(c);
+        }
+    }
+    if !run.is_empty() {
+        out.push_str(strongest_mark(&run));
+    }
+
+    out
+}
+
+/// The single mark that best represents a run of punctuation
+fn strongest_mark(run: &str) -> &'static str {
+    if run.len() >= 3 && run.chars().all(|c| c == '.') {
+        return "...";
+    }
+    for (mark, text) in [('?', "?"), ('!', "!"), ('.', "."), (';', ";"), (':', ":")] {
+        if run.contains(mark) {
+            return text;
+        }
+    }
+    ","
+}
+
+/// Make each standalone "a"/"an" agree with the word after it
+fn fix_articles(text: &str) -> String {
+    let mut words: Vec<String> = text.split(' ').map(str::to_string).collect();
+    for i in 0..words.len().saturating_sub(1) {
+        let article = match words[i].as_str() {
+            "a" | "an" => "a",
+            "A" | "An" => "A",
+            _ => continue,
+        };
+        let next = words[i + 1].trim_start_matches(|c: char| !c.is_alphanumeric());
+        let Some(first) = next.chars().next() else {
+            continue;
+        };
+        if !first.is_alphabetic() || is_initialism(next) {
+            continue;
+        }
+        let an = if takes_an(next) { "n" } else { "" };
+        words[i] = format!("{}{}", article, an);
+    }
+    words.join(" ")
+}
+
+/// True if `word` starts with a vowel sound
+fn takes_an(word: &str) -> bool {
+    let lower = word.to_lowercase();
+    if VOWEL_SOUND_PREFIXES.iter().any(|p| lower.starts_with(p)) {
+        return true;
+    }
+    let stem = lower.split(|c: char| !c.is_alphabetic()).next().unwrap_or_default();
+    if CONSONANT_SOUND_PREFIXES.iter().any(|p| lower.starts_with(p)) || CONSONANT_SOUND_WORDS.contains(&stem) {
+        return false;
+    }
+    lower.starts_with(['a', 'e', 'i', 'o', 'u'])
+}
+
+/// True if `word` is an initialism: two or more letters, all of them capitals ("FBI", "U.S.")
+fn is_initialism(word: &str) -> bool {
+    let letters: Vec<char> = word.chars().filter(|c| c.is_alphabetic()).collect();
+    letters.len() >= 2 && letters.iter().all(|c| c.is_uppercase())
+}
+
+/// Capitalize the first letter of the text and of every sentence
+fn capitalize_sentences(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut capitalize_next = true;
+
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '.' | '!' | '?' => capitalize_next = ends_sentence(&chars, i),
+            c if c.is_whitespace() || matches!(c, '"' | '\'' | '(' | '“' | '‘') => {}
+            c if capitalize_next && c.is_alphabetic() => {
+                out.extend(c.to_uppercase());
+                capitalize_next = false;
+                continue;
+            }
+            _ => capitalize_next = false,
+        }
+        out.push(c);
+    }
+
+    out
+}
+
+/// True if the mark at `chars[i]` ends a sentence
+/// It must be followed by whitespace; an ellipsis and the last full stop of a
+/// dotted initialism ("e.g.", "U.S.") don't count.
+fn ends_sentence(chars: &[char], i: usize) -> bool {
+    if !chars.get(i + 1).is_some_and(|c| c.is_whitespace()) {
+        return false;
+    }
+    if chars[i] != '.' {
+        return true;
+    }
+    if i > 0 && chars[i - 1] == '.' {
+        return false;
+    }
+    // A single letter between two full stops closes an initialism
+    !(i >= 2 && chars[i - 1].is_alphabetic() && chars[i - 2] == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::polish;
+
+    #[test]
+    fn capitalizes_sentence_starts() {
+        assert_eq!(polish("purr. the kitty waits! does it? yes"), "Purr. The kitty waits! Does it? Yes");
+        assert_eq!(polish("\"quiet,\" it says. (then) it naps."), "\"Quiet,\" it says. (Then) it naps.");
+    }
+
+    #[test]
+    fn ellipsis_does_not_end_a_sentence() {
+        assert_eq!(polish("wait... for it"), "Wait... for it");
+    }
+
+    #[test]
+    fn full_stop_without_whitespace_does_not_end_a_sentence() {
+        assert_eq!(polish("Visit qkitty.app today."), "Visit qkitty.app today.");
+        assert_eq!(polish("Version 1.5 is out."), "Version 1.5 is out.");
+    }
+
+    #[test]
+    fn initialisms_keep_their_case() {
+        assert_eq!(polish("Try a gift, e.g. this one."), "Try a gift, e.g. this one.");
+        assert_eq!(polish("e.g. this"), "E.g. this");
+        assert_eq!(polish("Ask i.e. the kitty."), "Ask i.e. the kitty.");
+        assert_eq!(polish("Made in the U.S. by cats."), "Made in the U.S. by cats.");
+    }
+
+    #[test]
+    fn articles_agree_with_the_next_word() {
+        assert_eq!(polish("a infinite field and an cat"), "An infinite field and a cat");
+        assert_eq!(polish("It takes a hour, an unicorn and a honest paw."), "It takes an hour, a unicorn and an honest paw.");
+    }
+
+    #[test]
+    fn articles_keep_an_before_other_un_and_on_words() {
+        assert_eq!(polish("a uninspired nap and a unintended leap"), "An uninspired nap and an unintended leap");
+        assert_eq!(polish("a onion, a onerous task and a onset"), "An onion, an onerous task and an onset");
+        assert_eq!(polish("an unique, unified universe"), "A unique, unified universe");
+        assert_eq!(polish("an one-time chance, an once-over and an one."), "A one-time chance, a once-over and a one.");
+    }
+
+    #[test]
+    fn articles_before_initialisms_are_left_alone() {
+        assert_eq!(polish("an FBI agent"), "An FBI agent");
+        assert_eq!(polish("Meet a NASA kitty and an NFT."), "Meet a NASA kitty and an NFT.");
+    }
+
+    #[test]
+    fn tidies_spacing_and_punctuation() {
+        assert_eq!(polish("in  harmony .. truly , really!?"), "In harmony. Truly, really?");
+        assert_eq!(polish("  space  "), "Space");
+    }
+}
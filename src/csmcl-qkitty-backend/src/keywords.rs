@@ -0,0 +1,422 @@
+// Community keyword registry
+//
+// Every keyword used in a signed-in wisdom request is recorded here so later visitors
+// can pick from what others have asked for:
+//   - `autocomplete` walks a prefix trie over the recorded keywords
+//   - `trending` ranks keywords by a usage score that halves every week without use,
+//     so a burst of recent requests outranks old popularity
+//   - each principal's most recent keywords are kept for quick reuse
+// Recording has its own per-minute rate limit, apart from the write rate limit, and
+// curators can remove unwanted keywords.
+//
+// Decay is applied lazily: a keyword stores its score as of its last use, and readers
+// decay it to the present. Two indexes are kept over the registry's keywords, neither
+// persisted but both rebuilt on restore: the prefix trie, and the keywords ordered by
+// score so the least trending can be evicted without a scan.
+use candid::{CandidType, Principal};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::error::KittyError;
+
+thread_local! {
+    static KEYWORD_REGISTRY: RefCell<KeywordRegistry> = RefCell::new(KeywordRegistry::default());
+    static KEYWORD_INDEX: RefCell<TrieNode> = RefCell::new(TrieNode::default());
+    static KEYWORD_RANKS: RefCell<BTreeSet<(Rank, String)>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Time for an unused keyword's trending score to halve, in nanoseconds (7 days)
+const TRENDING_HALF_LIFE_NS: f64 = 7.0 * 24.0 * 3600.0 * 1e9;
+
+/// Most keywords kept; recording a new one beyond this evicts the least trending
+const MAX_KEYWORDS: usize = 10_000;
+
+/// Recent keywords kept per principal
+const MAX_RECENT_KEYWORDS: usize = 10;
+
+/// Results returned by autocomplete and trending when no limit is given
+pub const DEFAULT_KEYWORD_LIMIT: u32 = 10;
+
+/// Most results autocomplete and trending return
+pub const MAX_KEYWORD_LIMIT: u32 = 50;
+
+/// KeywordStats is the usage of one community keyword
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct KeywordStats {
+    pub(crate) uses: u64,          // Times the keyword was requested
+    pub(crate) score: f64,         // Trending score as of last_used_at
+    pub(crate) first_used_at: u64, // IC time the keyword was first requested
+    pub(crate) last_used_at: u64,  // IC time the keyword was last requested
+}
+
+impl KeywordStats {
+    /// Trending score decayed to `now`
+    fn score_at(&self, now: u64) -> f64 {
+        let elapsed = now.saturating_sub(self.last_used_at) as f64;
+        self.score * 0.5f64.powf(elapsed / TRENDING_HALF_LIFE_NS)
+    }
+
+    /// Position in the eviction order
+    fn rank(&self) -> Rank {
+        // log2 of the score decayed (backwards) to time zero. Every score decays at the
+        // same rate, so comparing these compares the scores at any moment.
+        Rank(self.score.log2() + self.last_used_at as f64 / TRENDING_HALF_LIFE_NS)
+    }
+}
+
+/// Rank orders keywords by trending score, independently of the time it's compared at
+#[derive(Clone, Copy, Debug)]
+struct Rank(f64);
+
+impl PartialEq for Rank {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Rank {}
+
+impl PartialOrd for Rank {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Rank {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// KeywordRegistry holds the community keywords and each principal's recent keywords
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct KeywordRegistry {
+    pub(crate) keywords: HashMap<String, KeywordStats>, // Usage by keyword
+    pub(crate) recent: HashMap<Principal, Vec<String>>, // Most recent first
+}
+
+/// KeywordInfo is a community keyword as shown to users
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct KeywordInfo {
+    keyword: String,
+    uses: u64,           // Times the keyword was requested
+    trending_score: f64, // Recent usage, halving every week
+    last_used_at: u64,   // IC time the keyword was last requested
+    tagged_entries: u32, // Active templates, adjectives, phrases and words tagged with it
+}
+
+/// A node of the keyword prefix trie
+#[derive(Default)]
+struct TrieNode {
+    children: BTreeMap<char, TrieNode>,
+    terminal: bool, // A keyword ends here
+}
+
+impl TrieNode {
+    fn insert(&mut self, keyword: &str) {
+        let mut node = self;
+        for c in keyword.chars() {
+            node = node.children.entry(c).or_default();
+        }
+        node.terminal = true;
+    }
+
+    /// Remove `keyword`, pruning branches left empty; returns true if this node is now empty
+    fn remove(&mut self, mut chars: std::str::Chars) -> bool {
+        match chars.next() {
+            None => self.terminal = false,
+            Some(c) => {
+                if let Some(child) = self.children.get_mut(&c) {
+                    if child.remove(chars) {
+                        self.children.remove(&c);
+                    }
+                }
+            }
+        }
+        !self.terminal && self.children.is_empty()
+    }
+
+    /// Every keyword starting with `prefix`
+    fn completions(&self, prefix: &str) -> Vec<String> {
+        let mut node = self;
+        for c in prefix.chars() {
+            match node.children.get(&c) {
+                Some(child) => node = child,
+                None => return Vec::new(),
+            }
+        }
+        let mut found = Vec::new();
+        node.collect(&mut prefix.to_string(), &mut found);
+        found
+    }
+
+    fn collect(&self, word: &mut String, found: &mut Vec<String>) {
+        if self.terminal {
+            found.push(word.clone());
+        }
+        for (c, child) in &self.children {
+            word.push(*c);
+            child.collect(word, found);
+            word.pop();
+        }
+    }
+}
+
+/// Record `keywords` from one wisdom request by `caller`
+pub fn record(caller: Principal, keywords: &[String], now: u64) {
+    if keywords.is_empty() {
+        return;
+    }
+    KEYWORD_REGISTRY.with(|r| {
+        let mut registry = r.borrow_mut();
+        for keyword in keywords {
+            // Take the keyword out of the score order while its score changes
+            if let Some(stats) = registry.keywords.get(keyword) {
+                let rank = stats.rank();
+                KEYWORD_RANKS.with(|ranks| ranks.borrow_mut().remove(&(rank, keyword.clone())));
+            } else {
+                if registry.keywords.len() >= MAX_KEYWORDS {
+                    evict_least_trending(&mut registry);
+                }
+                KEYWORD_INDEX.with(|i| i.borrow_mut().insert(keyword));
+            }
+            let stats = registry.keywords.entry(keyword.clone()).or_insert(KeywordStats {
+                uses: 0,
+                score: 0.0,
+                first_used_at: now,
+                last_used_at: now,
+            });
+            stats.score = stats.score_at(now) + 1.0;
+            stats.uses += 1;
+            stats.last_used_at = now;
+            let rank = stats.rank();
+            KEYWORD_RANKS.with(|ranks| ranks.borrow_mut().insert((rank, keyword.clone())));
+        }
+
+        let recent = registry.recent.entry(caller).or_default();
+        // Walk the request backwards so its first keyword ends up most recent
+        for keyword in keywords.iter().rev() {
+            recent.retain(|k| k != keyword);
+            recent.insert(0, keyword.clone());
+        }
+        recent.truncate(MAX_RECENT_KEYWORDS);
+    });
+}
+
+fn evict_least_trending(registry: &mut KeywordRegistry) {
+    if let Some((_, keyword)) = KEYWORD_RANKS.with(|ranks| ranks.borrow_mut().pop_first()) {
+        registry.keywords.remove(&keyword);
+        KEYWORD_INDEX.with(|i| i.borrow_mut().remove(keyword.chars()));
+    }
+}
+
+/// Remove a keyword from the community pool and from everyone's recent keywords
+pub fn remove(keyword: &str) -> Result<(), KittyError> {
+    KEYWORD_REGISTRY.with(|r| {
+        let mut registry = r.borrow_mut();
+        let stats = registry
+            .keywords
+            .remove(keyword)
+            .ok_or_else(|| KittyError::NotFound(format!("No community keyword '{}'", keyword)))?;
+        KEYWORD_RANKS.with(|ranks| ranks.borrow_mut().remove(&(stats.rank(), keyword.to_string())));
+        KEYWORD_INDEX.with(|i| i.borrow_mut().remove(keyword.chars()));
+        for recent in registry.recent.values_mut() {
+            recent.retain(|k| k != keyword);
+        }
+        registry.recent.retain(|_, recent| !recent.is_empty());
+        Ok(())
+    })
+}
+
+/// Community keywords starting with `prefix`, most trending first
+/// `tagged` counts the content tagged with a keyword.
+pub fn autocomplete(prefix: &str, limit: u32, now: u64, tagged: impl Fn(&str) -> u32) -> Vec<KeywordInfo> {
+    let completions = KEYWORD_INDEX.with(|i| i.borrow().completions(prefix));
+    ranked(completions, limit, now, tagged)
+}
+
+/// The most trending community keywords
+pub fn trending(limit: u32, now: u64, tagged: impl Fn(&str) -> u32) -> Vec<KeywordInfo> {
+    let keywords = KEYWORD_REGISTRY.with(|r| r.borrow().keywords.keys().cloned().collect());
+    ranked(keywords, limit, now, tagged)
+}
+
+/// Sort `keywords` by trending score (then uses, then name) and describe the first `limit`
+fn ranked(keywords: Vec<String>, limit: u32, now: u64, tagged: impl Fn(&str) -> u32) -> Vec<KeywordInfo> {
+    KEYWORD_REGISTRY.with(|r| {
+        let registry = r.borrow();
+        let mut found: Vec<(String, &KeywordStats, f64)> = keywords
+            .into_iter()
+            .filter_map(|k| registry.keywords.get(&k).map(|stats| (k, stats, stats.score_at(now))))
+            .collect();
+        found.sort_by(|a, b| b.2.total_cmp(&a.2).then(b.1.uses.cmp(&a.1.uses)).then(a.0.cmp(&b.0)));
+        found
+            .into_iter()
+            .take(limit as usize)
+            .map(|(keyword, stats, score)| KeywordInfo {
+                tagged_entries: tagged(&keyword),
+                keyword,
+                uses: stats.uses,
+                trending_score: score,
+                last_used_at: stats.last_used_at,
+            })
+            .collect()
+    })
+}
+
+/// A principal's most recent keywords, most recent first
+pub fn recent(principal: &Principal) -> Vec<String> {
+    KEYWORD_REGISTRY.with(|r| r.borrow().recent.get(principal).cloned().unwrap_or_default())
+}
+
+/// Get a copy of the registry (used for persistence)
+pub fn snapshot() -> KeywordRegistry {
+    KEYWORD_REGISTRY.with(|r| r.borrow().clone())
+}
+
+/// Replace the registry and rebuild the indexes (used when restoring after an upgrade)
+pub fn restore(registry: KeywordRegistry) {
+    KEYWORD_INDEX.with(|i| {
+        let mut index = TrieNode::default();
+        for keyword in registry.keywords.keys() {
+            index.insert(keyword);
+        }
+        *i.borrow_mut() = index;
+    });
+    KEYWORD_RANKS.with(|ranks| {
+        *ranks.borrow_mut() = registry.keywords.iter().map(|(keyword, stats)| (stats.rank(), keyword.clone())).collect();
+    });
+    KEYWORD_REGISTRY.with(|r| *r.borrow_mut() = registry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_NS: u64 = 24 * 3600 * 1_000_000_000;
+
+    fn user(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn keywords(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    fn names(found: Vec<KeywordInfo>) -> Vec<String> {
+        found.into_iter().map(|info| info.keyword).collect()
+    }
+
+    #[test]
+    fn autocomplete_finds_keywords_by_prefix() {
+        record(user(1), &keywords(&["cat", "catnip", "caterpillar", "dog"]), 0);
+        record(user(1), &keywords(&["catnip"]), 0);
+
+        assert_eq!(names(autocomplete("cat", 10, 0, |_| 0)), ["catnip", "cat", "caterpillar"]);
+        assert_eq!(names(autocomplete("catn", 10, 0, |_| 0)), ["catnip"]);
+        assert_eq!(names(autocomplete("cow", 10, 0, |_| 0)), Vec::<String>::new());
+        assert_eq!(names(autocomplete("cat", 1, 0, |_| 0)), ["catnip"]);
+    }
+
+    #[test]
+    fn scores_halve_every_week_without_use() {
+        let stats = KeywordStats { uses: 4, score: 4.0, first_used_at: 0, last_used_at: 0 };
+        assert!((stats.score_at(7 * DAY_NS) - 2.0).abs() < 1e-9);
+        assert!((stats.score_at(14 * DAY_NS) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn recent_use_outranks_old_popularity() {
+        for _ in 0..3 {
+            record(user(1), &keywords(&["vintage"]), 0);
+        }
+        record(user(2), &keywords(&["fresh"]), 21 * DAY_NS);
+
+        // Three weeks on, 3 uses have decayed to 0.375 against 1 fresh use
+        assert_eq!(names(trending(10, 21 * DAY_NS, |_| 0)), ["fresh", "vintage"]);
+        // But at the time they were made, the three uses led
+        assert_eq!(names(trending(10, 0, |_| 0))[0], "vintage");
+    }
+
+    #[test]
+    fn rank_order_matches_decayed_scores() {
+        let old = KeywordStats { uses: 8, score: 8.0, first_used_at: 0, last_used_at: 0 };
+        let new = KeywordStats { uses: 1, score: 1.0, first_used_at: 0, last_used_at: 28 * DAY_NS };
+        assert!(old.score_at(28 * DAY_NS) < new.score_at(28 * DAY_NS));
+        assert!(old.rank() < new.rank());
+    }
+
+    #[test]
+    fn recording_at_capacity_evicts_the_least_trending() {
+        let filler: Vec<String> = (0..MAX_KEYWORDS - 1).map(|i| format!("k{}", i)).collect();
+        record(user(1), &filler, DAY_NS);
+        record(user(1), &keywords(&["stale"]), 0);
+        assert_eq!(snapshot().keywords.len(), MAX_KEYWORDS);
+
+        record(user(1), &keywords(&["newcomer"]), 2 * DAY_NS);
+
+        let registry = snapshot();
+        assert_eq!(registry.keywords.len(), MAX_KEYWORDS);
+        assert!(!registry.keywords.contains_key("stale"));
+        assert!(registry.keywords.contains_key("newcomer"));
+        assert!(names(autocomplete("sta", 10, 2 * DAY_NS, |_| 0)).is_empty());
+    }
+
+    #[test]
+    fn remove_drops_a_keyword_everywhere() {
+        record(user(1), &keywords(&["moon", "moonlight"]), 0);
+        record(user(2), &keywords(&["moon"]), 0);
+
+        assert_eq!(remove("moon"), Ok(()));
+        assert_eq!(names(autocomplete("moo", 10, 0, |_| 0)), ["moonlight"]);
+        assert_eq!(names(trending(10, 0, |_| 0)), ["moonlight"]);
+        assert_eq!(recent(&user(1)), ["moonlight"]);
+        assert!(recent(&user(2)).is_empty());
+        assert!(!snapshot().recent.contains_key(&user(2)));
+        assert!(matches!(remove("moon"), Err(KittyError::NotFound(_))));
+
+        // Removing a keyword keeps the longer keywords that share its prefix, and vice versa
+        assert_eq!(remove("moonlight"), Ok(()));
+        record(user(1), &keywords(&["moonlight"]), 0);
+        record(user(1), &keywords(&["moon"]), 0);
+        assert_eq!(remove("moonlight"), Ok(()));
+        assert_eq!(names(autocomplete("m", 10, 0, |_| 0)), ["moon"]);
+    }
+
+    #[test]
+    fn recent_keywords_are_most_recent_first_and_bounded() {
+        record(user(1), &keywords(&["a", "b"]), 0);
+        record(user(1), &keywords(&["c", "a"]), 1);
+        assert_eq!(recent(&user(1)), ["c", "a", "b"]);
+
+        let many: Vec<String> = (0..MAX_RECENT_KEYWORDS + 5).map(|i| format!("w{}", i)).collect();
+        record(user(1), &many, 2);
+        assert_eq!(recent(&user(1)).len(), MAX_RECENT_KEYWORDS);
+        assert_eq!(recent(&user(1))[0], "w0");
+    }
+
+    #[test]
+    fn restore_rebuilds_the_indexes() {
+        record(user(1), &keywords(&["purr", "purple"]), 0);
+        record(user(1), &keywords(&["purr"]), 0);
+        let saved = snapshot();
+
+        restore(KeywordRegistry::default());
+        assert!(autocomplete("pur", 10, 0, |_| 0).is_empty());
+
+        restore(saved);
+        assert_eq!(names(autocomplete("pur", 10, 0, |_| 0)), ["purr", "purple"]);
+        assert_eq!(remove("purple"), Ok(()));
+        assert_eq!(names(trending(10, 0, |_| 0)), ["purr"]);
+    }
+
+    #[test]
+    fn results_report_tagged_content() {
+        record(user(1), &keywords(&["zen"]), 0);
+        let found = trending(10, 0, |keyword| if keyword == "zen" { 3 } else { 0 });
+        assert_eq!(found[0].tagged_entries, 3);
+        assert_eq!(found[0].uses, 1);
+    }
+}
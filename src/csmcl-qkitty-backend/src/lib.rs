@@ -7,7 +7,32 @@ use candid::Principal; // Principal type for user identity
 use std::collections::HashMap;
 use std::cell::RefCell;
 
-/// Global state that shifts with ICP cycles
+use access::Role;
+use calendar::Moment;
+use error::KittyError;
+use random::Rng;
+use store::{Vocabulary, WisdomEntry, WisdomTemplateStore};
+use style::{Goal, Length, Preferences, Register};
+use template::{Detail, Placeholder, RenderContext, Template};
+
+mod access;
+mod calendar;
+mod config;
+mod error;
+mod grammar;
+mod keywords;
+mod migrations;
+mod persistence;
+mod personal;
+mod pulse;
+mod random;
+mod served;
+mod store;
+mod style;
+mod template;
+mod validation;
+
+// Global state that shifts with ICP cycles
 thread_local! {
     static GLOBAL_STATE: RefCell<KittyState> = RefCell::new(KittyState::default());
     static WISDOM_TEMPLATES: RefCell<WisdomTemplateStore> = RefCell::new(WisdomTemplateStore::default());
@@ -35,19 +60,6 @@ impl Default for KittyState {
     }
 }
 
-/// WisdomTemplateStore holds all the templates and phrases for wisdom generation
-#[derive(CandidType, Deserialize, Clone, Default)]
-pub struct WisdomTemplateStore {
-    // Templates organized by context
-    templates: HashMap<String, Vec<String>>,
-    
-    // Quantum adjectives by state
-    quantum_adjectives: HashMap<String, Vec<String>>,
-    
-    // Zen phrases by mood
-    zen_phrases: HashMap<String, Vec<String>>,
-}
-
 /// QuantumResponse represents the structured data our canister returns
 /// 
 /// - CandidType: Makes this struct serializable to Candid format for ICP communication
@@ -66,10 +78,190 @@ pub struct QuantumResponse {
 /// - Deserialize: Allows this struct to be created from incoming data
 #[derive(CandidType, Deserialize)]
 pub struct WisdomResponse {
-    content: String,       // The wisdom content
-    quantum_state: String, // The current quantum state
-    energy_level: u8,      // Energy level from 1-10
-    zen_mood: String,      // Current zen mood
+    content: String,          // The wisdom content
+    quantum_state: String,    // The current quantum state
+    energy_level: u8,         // Energy level from 1-10
+    zen_mood: String,         // Current zen mood
+    template_id: Option<u64>, // ID of the template used (None if the kitty had no templates)
+    context: String,          // Context the template was drawn from
+    choices: WisdomChoices,   // Which of the requested style preferences were honored
+    seed: u64,                // Seed every random choice was drawn from
+    generated_at: u64,        // IC time the wisdom was generated at (used for calendar placeholders)
+}
+
+/// WisdomChoices reports how the kitty matched the style of a wisdom request
+/// A preference that wasn't requested is never honored.
+#[derive(CandidType, Deserialize)]
+pub struct WisdomChoices {
+    goal_honored: bool,            // The template is tagged for the requested goal
+    register_honored: bool,        // The template is tagged for the requested register
+    keywords_honored: Vec<String>, // Requested keywords the template or the words filled into it are tagged with
+    length: Length,                // How long the wisdom reads
+    length_honored: bool,          // The wisdom has the requested length
+}
+
+impl WisdomChoices {
+    /// Choices for `content` crafted without any style preferences
+    fn unstyled(content: &str) -> Self {
+        Self {
+            goal_honored: false,
+            register_honored: false,
+            keywords_honored: Vec::new(),
+            length: Length::of(content),
+            length_honored: false,
+        }
+    }
+}
+
+/// WeightedContext is a context to draw wisdom from and how strongly to favour it
+#[derive(CandidType, Deserialize)]
+pub struct WeightedContext {
+    context: String, // The context key
+    weight: u32,     // Relative chance of drawing from this context (0 leaves it out)
+}
+
+/// WisdomRequest asks for wisdom, optionally written as a gift for someone
+///
+/// Without a recipient the wisdom addresses the reader as "you".
+#[derive(CandidType, Deserialize)]
+pub struct WisdomRequest {
+    kitty_name: String,              // The kitty sharing the wisdom
+    contexts: Vec<WeightedContext>,  // Contexts to draw templates from (the default context if empty)
+    recipient_name: Option<String>,  // Who the wisdom is for
+    sender_name: Option<String>,     // Who is giving it
+    relationship: Option<String>,    // How the sender knows the recipient, e.g. "sister" or "mentor"
+    recipient_age: Option<u8>,       // The recipient's age, e.g. for birthdays
+    utc_offset_minutes: Option<i16>, // The caller's UTC offset, for calendar placeholders (UTC if None)
+    goal: Option<Goal>,              // What the wisdom is for, e.g. a meeting opener
+    register: Option<Register>,      // The tone to write in
+    keywords: Vec<String>,           // Themes the wisdom should touch on (at most 5)
+    length: Option<Length>,          // How long the wisdom should read
+}
+
+/// WisdomReplay is everything needed to render a wisdom again exactly
+/// The seed, template, kitty state and time all come from the original WisdomResponse.
+#[derive(CandidType, Deserialize)]
+pub struct WisdomReplay {
+    request: WisdomRequest, // The original request (its contexts are not used)
+    seed: u64,              // WisdomResponse.seed
+    template_id: u64,       // WisdomResponse.template_id
+    quantum_state: String,  // WisdomResponse.quantum_state
+    energy_level: u8,       // WisdomResponse.energy_level
+    zen_mood: String,       // WisdomResponse.zen_mood
+    generated_at: u64,      // WisdomResponse.generated_at
+}
+
+/// Recipient is the validated personalization of a wisdom request
+#[derive(Default)]
+struct Recipient {
+    name: Option<String>,
+    sender: Option<String>,
+    relationship: Option<String>,
+    age: Option<u8>,
+}
+
+/// Oldest recipient age accepted in a wisdom request
+const MAX_RECIPIENT_AGE: u8 = 150;
+
+/// Most contexts a single wisdom request may blend
+const MAX_REQUEST_CONTEXTS: usize = 10;
+
+/// Most keywords a single wisdom request may carry
+const MAX_REQUEST_KEYWORDS: usize = 5;
+
+/// CraftedWisdom is generated wisdom text and the template it came from
+struct CraftedWisdom {
+    content: String,
+    template_id: Option<u64>,
+    context: String,
+    choices: WisdomChoices,
+}
+
+/// WisdomContext is what a template is rendered against when crafting wisdom
+struct WisdomContext<'a> {
+    state: &'a KittyState,
+    store: &'a WisdomTemplateStore, // Source of slot dictionary words
+    context: &'a str,               // The wisdom context being generated for
+    quantum: String,                // Chosen quantum adjective
+    zen: String,                    // Chosen zen phrase
+    kitty: &'a str,                 // The kitty's name
+    recipient: &'a Recipient,       // Who the wisdom is for, if it's a gift
+    preferences: &'a Preferences,   // Requested style; words tagged with its keywords are preferred
+    moment: Moment,                 // The caller's local date and time
+    rng: Rng,                       // Picks {a|b|c} alternatives and words
+    honored: Vec<String>,           // Keywords honored by the words filled in so far
+}
+
+impl RenderContext for WisdomContext<'_> {
+    fn fill(&mut self, placeholder: &Placeholder) -> String {
+        match placeholder {
+            Placeholder::Quantum => self.quantum.clone(),
+            Placeholder::Zen => self.zen.clone(),
+            Placeholder::Name => self.recipient.name.clone().unwrap_or_else(|| "you".to_string()),
+            Placeholder::Kitty => self.kitty.to_string(),
+            Placeholder::Sender => self.recipient.sender.clone().unwrap_or_else(|| "a friend".to_string()),
+            Placeholder::Relationship => self.recipient.relationship.clone().unwrap_or_else(|| "friend".to_string()),
+            Placeholder::Age => self.recipient.age.map(|age| age.to_string()).unwrap_or_default(),
+            Placeholder::AgeOrdinal => self.recipient.age.map(|age| template::ordinal(age.into())).unwrap_or_default(),
+            Placeholder::Pronoun(pronoun) => pronoun.form(self.recipient.name.is_none()).to_string(),
+            Placeholder::Weekday => self.moment.weekday().to_string(),
+            Placeholder::Date => self.moment.date(),
+            Placeholder::Day => template::ordinal(self.moment.day()),
+            Placeholder::Month => self.moment.month().to_string(),
+            Placeholder::Year => self.moment.year().to_string(),
+            Placeholder::Season => self.moment.season().to_string(),
+            Placeholder::TimeOfDay => self.moment.time_of_day().to_string(),
+            Placeholder::Dictionary(name) => {
+                let key = match self.store.dictionary(name).map(|d| d.keyed_by) {
+                    Ok(store::SlotKey::State) => self.state.quantum_state.as_str(),
+                    Ok(store::SlotKey::Mood) => self.state.zen_mood.as_str(),
+                    Ok(store::SlotKey::Context) => self.context,
+                    Ok(store::SlotKey::Unkeyed) | Err(_) => store::ANY_KEY,
+                };
+                let words = self.store.active_words(name, key);
+                let words = self.preferences.best_keyword_matches(&words);
+                if words.is_empty() {
+                    return String::new();
+                }
+                let word = words[self.choose(words.len())];
+                self.honored.extend(self.preferences.fit(&word.tags).keywords);
+                word.text.clone()
+            }
+        }
+    }
+
+    fn choose(&mut self, options: usize) -> usize {
+        self.rng.below(options)
+    }
+
+    fn energy_level(&self) -> u8 {
+        self.state.energy_level
+    }
+
+    fn quantum_state(&self) -> &str {
+        &self.state.quantum_state
+    }
+
+    fn zen_mood(&self) -> &str {
+        &self.state.zen_mood
+    }
+
+    fn age(&self) -> Option<u8> {
+        self.recipient.age
+    }
+
+    fn knows(&self, detail: Detail) -> bool {
+        match detail {
+            Detail::Recipient => self.recipient.name.is_some(),
+            Detail::Sender => self.recipient.sender.is_some(),
+            Detail::Relationship => self.recipient.relationship.is_some(),
+            Detail::Age => self.recipient.age.is_some(),
+        }
+    }
+
+    fn moment(&self) -> &Moment {
+        &self.moment
+    }
 }
 
 /// A query function that generates a quantum-themed greeting
@@ -81,22 +273,14 @@ pub struct WisdomResponse {
 /// 
 /// The #[ic_cdk::query] macro exposes this function to the ICP network
 #[ic_cdk::query]
-fn quantum_greet(name: String) -> QuantumResponse {
-    // First, update the global state to ensure it's current
-    let state = GLOBAL_STATE.with(|s| {
-        let state = s.borrow().clone();
-        // If state is stale (older than 30 minutes), update it
-        if time() - state.last_updated > 1_800_000_000_000 {
-            drop(state); // Drop the borrowed reference before calling update_kitty_state
-            update_kitty_state()
-        } else {
-            state
-        }
-    });
+fn quantum_greet(name: String) -> Result<QuantumResponse, KittyError> {
+    let name = validation::name("name", &name)?;
     
-    // Generate pseudo-randomness based on current IC time
-    let now = time();
-    let random_value = (now % 4) as u128;
+    // Read the global state, kept current by the pulse timer, as the caller's own kitty shows it
+    let global = GLOBAL_STATE.with(|s| s.borrow().clone());
+    let state = personal::state_for(access::authenticated_caller().ok(), &global, &config::current());
+    
+    let mut rng = Rng::from_seed(random::call_seed(caller(), name.as_bytes(), time()));
     
     // Select a greeting based on the random value and zen mood
     let mood_greeting = match state.zen_mood.as_str() {
@@ -126,17 +310,17 @@ fn quantum_greet(name: String) -> QuantumResponse {
         ],
     };
     
-    // Select greeting based on time and mood
-    let greeting_index = (now / 1_000_000_000) as usize % 2;
-    let greeting = format!("{}", mood_greeting[greeting_index].replace("{}", &name));
+    // Select a greeting for the mood at random
+    let greeting_index = rng.below(mood_greeting.len());
+    let greeting = mood_greeting[greeting_index].replace("{}", &name);
     
     // Return the complete QuantumResponse using the current global state
-    QuantumResponse {
+    Ok(QuantumResponse {
         greeting,
         quantum_state: state.quantum_state,
         energy_level: state.energy_level,
         zen_mood: state.zen_mood,
-    }
+    })
 }
 
 /// A simpler greeting function kept for compatibility and demonstration
@@ -149,238 +333,996 @@ fn greet(name: String) -> String {
 }
 
 
-/// Update global state based on IC time cycles
+/// Get the global state as of the latest pulse
+#[ic_cdk::query]
+pub fn get_kitty_state() -> KittyState {
+    GLOBAL_STATE.with(|s| s.borrow().clone())
+}
+
+/// Update global state based on IC time cycles (controllers only)
+/// 
+/// The pulse timer does this automatically; calling it directly forces an extra pulse,
+/// which is recorded in the pulse history like any other.
 #[ic_cdk::update]
-pub fn update_kitty_state() -> KittyState {
+pub fn update_kitty_state() -> Result<KittyState, KittyError> {
+    access::require_role(Role::Controller)?;
+    Ok(pulse_kitty_state())
+}
+
+/// Advance the global state to match the current IC time and record the pulse
+fn pulse_kitty_state() -> KittyState {
     ensure_templates_loaded(); // Ensure templates are loaded before updating state
-    let now = time();
-    
-    // Quantum states rotate every 4 hours (based on IC time)
-    let states = vec![
-        "Superposition",   // Possibilities, choices
-        "Entangled",       // Connections, relationships
-        "Coherent",        // Clarity, focus
-        "Resonating",      // Harmony, balance
-        "Folded",          // Time, perspective
-    ];
-    
-    // Zen moods that influence the kitty's communication style
-    let moods = vec![
-        "Tranquil",        // Peaceful, calm
-        "Contemplative",   // Thoughtful, reflective
-        "Playful",         // Curious, energetic
-        "Mysterious",      // Enigmatic, deep
-        "Enlightened",     // Wise, insightful
-    ];
-    
-    // Calculate state based on IC time cycles
-    let hours_cycle = (now / 1_000_000_000 / 3600) as usize;
-    let state_index = (hours_cycle / 4) % states.len();
-    let mood_index = (hours_cycle / 6) % moods.len();
-    
-    // Energy level fluctuates more frequently (every 30 minutes)
-    let energy_cycle = (now / 1_000_000_000 / 1800) as usize;
-    let energy_level = ((energy_cycle % 10) + 1) as u8;
-    
-    let new_state = KittyState {
-        quantum_state: states[state_index].to_string(),
-        energy_level,
-        zen_mood: moods[mood_index].to_string(),
-        last_updated: now,
-    };
+    let new_state = kitty_state_at(time(), &config::current());
     
     GLOBAL_STATE.with(|state| {
         *state.borrow_mut() = new_state.clone();
     });
+    pulse::record_pulse(&new_state);
     
     new_state
 }
 
-/// Add a wisdom template for a specific context
+/// The global kitty's state at IC time `now` under `config`
+fn kitty_state_at(now: u64, config: &config::KittyConfig) -> KittyState {
+    // Calculate state based on IC time cycles and the configured rotation periods
+    let seconds = now / 1_000_000_000;
+    let state_index = (seconds / config.state_period_secs) as usize % config.quantum_states.len();
+    let mood_index = (seconds / config.mood_period_secs) as usize % config.zen_moods.len();
+    
+    // Energy level fluctuates more frequently, cycling through the configured bounds
+    let energy_span = (config.energy_max - config.energy_min) as u64 + 1;
+    let energy_cycle = seconds / config.energy_period_secs;
+    let energy_level = config.energy_min + (energy_cycle % energy_span) as u8;
+    
+    KittyState {
+        quantum_state: config.quantum_states[state_index].clone(),
+        energy_level,
+        zen_mood: config.zen_moods[mood_index].clone(),
+        last_updated: now,
+    }
+}
+
+/// Get the most recent pulses of the global state, oldest first
+#[ic_cdk::query]
+pub fn get_pulse_history() -> Vec<pulse::PulseRecord> {
+    pulse::history()
+}
+
+/// Get the number of seconds between timer pulses
+#[ic_cdk::query]
+pub fn get_pulse_interval() -> u64 {
+    pulse::interval_secs()
+}
+
+/// Get the current state catalogue, mood catalogue, rotation periods and energy bounds
+#[ic_cdk::query]
+pub fn get_kitty_config() -> config::KittyConfig {
+    config::current()
+}
+
+/// Replace the kitty configuration (controllers only)
+/// The global state is re-pulsed immediately so it reflects the new catalogue.
+#[ic_cdk::update]
+pub fn set_kitty_config(new_config: config::KittyConfig) -> Result<(), KittyError> {
+    access::require_role(Role::Controller)?;
+    config::apply(new_config)?;
+    pulse_kitty_state();
+    Ok(())
+}
+
+/// Change the number of seconds between timer pulses (controllers only)
+#[ic_cdk::update]
+pub fn set_pulse_interval(seconds: u64) -> Result<(), KittyError> {
+    access::require_role(Role::Controller)?;
+    if seconds < pulse::MIN_PULSE_INTERVAL_SECS {
+        return Err(KittyError::InvalidInput(format!(
+            "Pulse interval must be at least {} seconds",
+            pulse::MIN_PULSE_INTERVAL_SECS
+        )));
+    }
+    pulse::set_interval_secs(seconds);
+    Ok(())
+}
+
+/// Grant a role to a principal
+/// Admins can grant Curator; controllers can also grant Admin
+#[ic_cdk::update]
+pub fn grant_role(principal: Principal, role: Role) -> Result<(), KittyError> {
+    access::grant(principal, role)
+}
+
+/// Revoke any granted role from a principal, making them a plain User again
+#[ic_cdk::update]
+pub fn revoke_role(principal: Principal) -> Result<(), KittyError> {
+    access::revoke(principal)
+}
+
+/// Get the caller's effective role
+#[ic_cdk::query]
+pub fn get_my_role() -> Role {
+    access::role_of(&caller())
+}
+
+/// List all explicitly granted roles (admins only)
+#[ic_cdk::query]
+pub fn list_role_assignments() -> Result<Vec<access::RoleAssignment>, KittyError> {
+    access::require_role(Role::Admin)?;
+    Ok(access::assignments())
+}
+
+/// Add a wisdom template for a specific context (curators only)
+/// Returns the new template's ID
+#[ic_cdk::update]
+pub fn add_wisdom_template(context: String, template: String) -> Result<u64, KittyError> {
+    let context = validation::context_key(&context)?;
+    let template = sanitize_entry(Vocabulary::Template, &template)?;
+    curate(|store| store.add(Vocabulary::Template, context, template, caller(), time()))
+}
+
+/// Add a quantum adjective for a specific quantum state (curators only)
+/// Returns the new adjective's ID
+#[ic_cdk::update]
+pub fn add_quantum_adjective(state: String, adjective: String) -> Result<u64, KittyError> {
+    let state = validation::catalogue_key("state", &state)?;
+    let adjective = sanitize_entry(Vocabulary::Adjective, &adjective)?;
+    if !config::current().quantum_states.contains(&state) {
+        return Err(KittyError::NotFound(format!("Unknown quantum state '{}'", state)));
+    }
+    curate(|store| store.add(Vocabulary::Adjective, state, adjective, caller(), time()))
+}
+
+/// Add a zen phrase for a specific zen mood (curators only)
+/// Returns the new phrase's ID
+#[ic_cdk::update]
+pub fn add_zen_phrase(mood: String, phrase: String) -> Result<u64, KittyError> {
+    let mood = validation::catalogue_key("mood", &mood)?;
+    let phrase = sanitize_entry(Vocabulary::Phrase, &phrase)?;
+    if !config::current().zen_moods.contains(&mood) {
+        return Err(KittyError::NotFound(format!("Unknown zen mood '{}'", mood)));
+    }
+    curate(|store| store.add(Vocabulary::Phrase, mood, phrase, caller(), time()))
+}
+
+/// Replace the text of a wisdom template (curators only)
+#[ic_cdk::update]
+pub fn update_wisdom_template(id: u64, template: String) -> Result<(), KittyError> {
+    let template = sanitize_entry(Vocabulary::Template, &template)?;
+    curate(|store| store.update(Vocabulary::Template, id, template, time()))
+}
+
+/// Replace the text of a quantum adjective (curators only)
 #[ic_cdk::update]
-pub fn add_wisdom_template(context: String, template: String) {
+pub fn update_quantum_adjective(id: u64, adjective: String) -> Result<(), KittyError> {
+    let adjective = sanitize_entry(Vocabulary::Adjective, &adjective)?;
+    curate(|store| store.update(Vocabulary::Adjective, id, adjective, time()))
+}
+
+/// Replace the text of a zen phrase (curators only)
+#[ic_cdk::update]
+pub fn update_zen_phrase(id: u64, phrase: String) -> Result<(), KittyError> {
+    let phrase = sanitize_entry(Vocabulary::Phrase, &phrase)?;
+    curate(|store| store.update(Vocabulary::Phrase, id, phrase, time()))
+}
+
+/// Replace the tags of a wisdom template (curators only)
+/// Tags follow the same rules as context keys; at most 10 are allowed and duplicates are dropped
+#[ic_cdk::update]
+pub fn set_template_tags(id: u64, tags: Vec<String>) -> Result<(), KittyError> {
+    let tags = clean_tags(&tags)?;
+    curate(|store| store.set_tags(Vocabulary::Template, id, tags, time()))
+}
+
+/// Set how likely a wisdom template is to be picked relative to the others that fit
+/// a request (curators only). Templates start at 1; 0 disables a template so it's never
+/// served (a context whose templates are all disabled falls back like an empty one),
+/// and at most 100 is allowed.
+#[ic_cdk::update]
+pub fn set_template_weight(id: u64, weight: u32) -> Result<(), KittyError> {
+    curate(|store| store.set_weight(id, weight, time()))
+}
+
+/// Replace the keyword tags of a quantum adjective (curators only), as for set_template_tags
+#[ic_cdk::update]
+pub fn set_quantum_adjective_tags(id: u64, tags: Vec<String>) -> Result<(), KittyError> {
+    let tags = clean_tags(&tags)?;
+    curate(|store| store.set_tags(Vocabulary::Adjective, id, tags, time()))
+}
+
+/// Replace the keyword tags of a zen phrase (curators only), as for set_template_tags
+#[ic_cdk::update]
+pub fn set_zen_phrase_tags(id: u64, tags: Vec<String>) -> Result<(), KittyError> {
+    let tags = clean_tags(&tags)?;
+    curate(|store| store.set_tags(Vocabulary::Phrase, id, tags, time()))
+}
+
+/// Replace the keyword tags of a slot dictionary word (curators only), as for set_template_tags
+#[ic_cdk::update]
+pub fn set_slot_word_tags(id: u64, tags: Vec<String>) -> Result<(), KittyError> {
+    let tags = clean_tags(&tags)?;
+    curate(|store| store.set_tags(Vocabulary::Word, id, tags, time()))
+}
+
+/// Validate tags, dropping duplicates
+fn clean_tags(tags: &[String]) -> Result<Vec<String>, KittyError> {
+    let mut cleaned: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = validation::tag(tag)?;
+        if !cleaned.contains(&tag) {
+            cleaned.push(tag);
+        }
+    }
+    Ok(cleaned)
+}
+
+/// Soft-delete a wisdom template (curators only); restore with restore_wisdom_template
+#[ic_cdk::update]
+pub fn delete_wisdom_template(id: u64) -> Result<(), KittyError> {
+    curate(|store| store.delete(Vocabulary::Template, id, time()))
+}
+
+/// Soft-delete a quantum adjective (curators only); restore with restore_quantum_adjective
+#[ic_cdk::update]
+pub fn delete_quantum_adjective(id: u64) -> Result<(), KittyError> {
+    curate(|store| store.delete(Vocabulary::Adjective, id, time()))
+}
+
+/// Soft-delete a zen phrase (curators only); restore with restore_zen_phrase
+#[ic_cdk::update]
+pub fn delete_zen_phrase(id: u64) -> Result<(), KittyError> {
+    curate(|store| store.delete(Vocabulary::Phrase, id, time()))
+}
+
+/// Restore a soft-deleted wisdom template (curators only)
+#[ic_cdk::update]
+pub fn restore_wisdom_template(id: u64) -> Result<(), KittyError> {
+    curate(|store| store.restore(Vocabulary::Template, id))
+}
+
+/// Restore a soft-deleted quantum adjective (curators only)
+#[ic_cdk::update]
+pub fn restore_quantum_adjective(id: u64) -> Result<(), KittyError> {
+    curate(|store| store.restore(Vocabulary::Adjective, id))
+}
+
+/// Restore a soft-deleted zen phrase (curators only)
+#[ic_cdk::update]
+pub fn restore_zen_phrase(id: u64) -> Result<(), KittyError> {
+    curate(|store| store.restore(Vocabulary::Phrase, id))
+}
+
+/// Soft-delete every template in a context (curators only)
+/// Returns the number of templates deleted
+#[ic_cdk::update]
+pub fn delete_context(context: String) -> Result<u32, KittyError> {
+    let context = validation::context_key(&context)?;
+    curate(|store| store.delete_context(&context, time()))
+}
+
+/// Restore every deleted template in a context (curators only)
+/// Returns the number of templates restored; duplicates of active templates stay deleted
+#[ic_cdk::update]
+pub fn restore_context(context: String) -> Result<u32, KittyError> {
+    let context = validation::context_key(&context)?;
+    curate(|store| store.restore_context(&context))
+}
+
+/// Define a slot dictionary that templates can reference as `{name}` (curators only)
+/// `keyed_by` chooses whether its words vary by quantum state, zen mood or context
+#[ic_cdk::update]
+pub fn define_slot_dictionary(name: String, keyed_by: store::SlotKey) -> Result<(), KittyError> {
+    let name = validation::dictionary_name(&name)?;
+    if Placeholder::is_built_in(&name) {
+        return Err(KittyError::InvalidInput(format!("'{{{}}}' is a built-in placeholder", name)));
+    }
+    curate(|store| store.define_dictionary(name, keyed_by))
+}
+
+/// Add a word to a slot dictionary (curators only)
+/// `key` is the state, mood or context it applies to; leave it empty for words that apply to any
+/// Returns the new word's ID
+#[ic_cdk::update]
+pub fn add_slot_word(dictionary: String, key: Option<String>, word: String) -> Result<u64, KittyError> {
+    let dictionary = validation::dictionary_name(&dictionary)?;
+    let word = sanitize_entry(Vocabulary::Word, &word)?;
+    let key = slot_key(&dictionary, key.as_deref())?;
+    curate(|store| store.add_word(&dictionary, key, word, caller(), time()))
+}
+
+/// Replace the text of a slot dictionary word (curators only)
+#[ic_cdk::update]
+pub fn update_slot_word(id: u64, word: String) -> Result<(), KittyError> {
+    let word = sanitize_entry(Vocabulary::Word, &word)?;
+    curate(|store| store.update(Vocabulary::Word, id, word, time()))
+}
+
+/// Soft-delete a slot dictionary word (curators only); restore with restore_slot_word
+#[ic_cdk::update]
+pub fn delete_slot_word(id: u64) -> Result<(), KittyError> {
+    curate(|store| store.delete(Vocabulary::Word, id, time()))
+}
+
+/// Restore a soft-deleted slot dictionary word (curators only)
+#[ic_cdk::update]
+pub fn restore_slot_word(id: u64) -> Result<(), KittyError> {
+    curate(|store| store.restore(Vocabulary::Word, id))
+}
+
+/// Check a word's key against what its dictionary is keyed by
+/// Returns the stored key (store::ANY_KEY when the word applies to any state, mood or context)
+fn slot_key(dictionary: &str, key: Option<&str>) -> Result<String, KittyError> {
     ensure_templates_loaded(); // Ensure templates are loaded
-    WISDOM_TEMPLATES.with(|store| {
-        let mut store = store.borrow_mut();
-        let templates = store.templates.entry(context).or_insert_with(Vec::new);
-        
-        // Avoid duplicates
-        if !templates.contains(&template) {
-            templates.push(template);
+    let keyed_by = WISDOM_TEMPLATES.with(|store| store.borrow().dictionary(dictionary).map(|d| d.keyed_by))?;
+    let Some(key) = key else {
+        return Ok(store::ANY_KEY.to_string());
+    };
+    match keyed_by {
+        store::SlotKey::Unkeyed => Err(KittyError::InvalidInput(format!(
+            "Slot dictionary '{}' isn't keyed; leave the key empty",
+            dictionary
+        ))),
+        store::SlotKey::State => {
+            let state = validation::catalogue_key("state", key)?;
+            if !config::current().quantum_states.contains(&state) {
+                return Err(KittyError::NotFound(format!("Unknown quantum state '{}'", state)));
+            }
+            Ok(state)
+        }
+        store::SlotKey::Mood => {
+            let mood = validation::catalogue_key("mood", key)?;
+            if !config::current().zen_moods.contains(&mood) {
+                return Err(KittyError::NotFound(format!("Unknown zen mood '{}'", mood)));
+            }
+            Ok(mood)
+        }
+        store::SlotKey::Context => validation::context_key(key),
+    }
+}
+
+/// List soft-deleted templates, adjectives, phrases and words (curators only)
+#[ic_cdk::query]
+pub fn list_deleted_entries() -> Result<Vec<store::DeletedEntry>, KittyError> {
+    access::require_role(Role::Curator)?;
+    Ok(WISDOM_TEMPLATES.with(|store| store.borrow().deleted_entries()))
+}
+
+/// Run a change against the template store on behalf of a curator
+fn curate<T>(change: impl FnOnce(&mut WisdomTemplateStore) -> Result<T, KittyError>) -> Result<T, KittyError> {
+    access::require_role(Role::Curator)?;
+    access::check_rate_limit(&caller())?;
+    ensure_templates_loaded(); // Ensure templates are loaded
+    WISDOM_TEMPLATES.with(|store| change(&mut store.borrow_mut()))
+}
+
+/// Validate the text of a template, adjective or phrase
+/// Templates must also parse, and may only branch on configured states and moods,
+/// so typos are caught before they're stored
+fn sanitize_entry(vocabulary: Vocabulary, text: &str) -> Result<String, KittyError> {
+    match vocabulary {
+        Vocabulary::Template => {
+            let text = validation::template(text)?;
+            let parsed = template::validate(&text)?;
+            let mut dictionaries: Vec<String> = WISDOM_TEMPLATES.with(|store| store.borrow().dictionaries.keys().cloned().collect());
+            dictionaries.sort();
+            parsed.check_dictionaries(&dictionaries)?;
+            let config = config::current();
+            if let Some(state) = parsed.referenced_states().into_iter().find(|s| !config.quantum_states.contains(s)) {
+                return Err(KittyError::InvalidInput(format!("template: unknown quantum state '{}'", state)));
+            }
+            if let Some(mood) = parsed.referenced_moods().into_iter().find(|m| !config.zen_moods.contains(m)) {
+                return Err(KittyError::InvalidInput(format!("template: unknown zen mood '{}'", mood)));
+            }
+            Ok(text)
         }
+        Vocabulary::Adjective => validation::sanitize_text("adjective", text, validation::MAX_ADJECTIVE_CHARS),
+        Vocabulary::Phrase => validation::sanitize_text("phrase", text, validation::MAX_PHRASE_CHARS),
+        Vocabulary::Word => validation::sanitize_text("word", text, validation::MAX_PHRASE_CHARS),
+    }
+}
+
+/// Query methods to get templates
+#[ic_cdk::query]
+pub fn get_templates_for_context(context: String) -> Result<Vec<store::WisdomTemplate>, KittyError> {
+    let context = validation::context_key(&context)?;
+    ensure_templates_loaded(); // Ensure templates are loaded
+    WISDOM_TEMPLATES.with(|store| {
+        store.borrow().active_templates(&context)
+            .ok_or_else(|| KittyError::NotFound(format!("Unknown context '{}'", context)))
     })
 }
 
-#[ic_cdk::update]
-pub fn add_quantum_adjective(state: String, adjective: String) {
+/// List every context that has active templates, with template counts
+#[ic_cdk::query]
+pub fn list_contexts() -> Vec<store::KeyCount> {
+    ensure_templates_loaded(); // Ensure templates are loaded
+    WISDOM_TEMPLATES.with(|store| store.borrow().key_counts(Vocabulary::Template))
+}
+
+/// List the configured quantum states with their adjective counts
+#[ic_cdk::query]
+pub fn list_quantum_states() -> Vec<store::KeyCount> {
+    ensure_templates_loaded(); // Ensure templates are loaded
+    catalogue_counts(&config::current().quantum_states, Vocabulary::Adjective)
+}
+
+/// List the configured zen moods with their phrase counts
+#[ic_cdk::query]
+pub fn list_zen_moods() -> Vec<store::KeyCount> {
     ensure_templates_loaded(); // Ensure templates are loaded
+    catalogue_counts(&config::current().zen_moods, Vocabulary::Phrase)
+}
+
+/// Count the active entries for each catalogue name, keeping catalogue order
+fn catalogue_counts(names: &[String], vocabulary: Vocabulary) -> Vec<store::KeyCount> {
     WISDOM_TEMPLATES.with(|store| {
-        let mut store = store.borrow_mut();
-        let adjectives = store.quantum_adjectives.entry(state).or_insert_with(Vec::new);
-        
-        if !adjectives.contains(&adjective) {
-            adjectives.push(adjective);
-        }
+        let store = store.borrow();
+        names.iter()
+            .map(|name| store::KeyCount::new(name.clone(), store.active_count(vocabulary, name)))
+            .collect()
     })
 }
 
-#[ic_cdk::update]
-pub fn add_zen_phrase(mood: String, phrase: String) {
+/// List templates a page at a time, optionally restricted to one context and filtered by substring
+#[ic_cdk::query]
+pub fn list_templates(request: store::PageRequest) -> Result<store::TemplatePage, KittyError> {
+    let context = request.context.as_deref().map(validation::context_key).transpose()?;
+    let filter = request.filter.as_deref()
+        .map(|f| validation::sanitize_text("filter", f, validation::MAX_NAME_CHARS))
+        .transpose()?;
+    let page_size = request.page_size.unwrap_or(store::DEFAULT_PAGE_SIZE);
+    if page_size == 0 || page_size > store::MAX_PAGE_SIZE {
+        return Err(KittyError::InvalidInput(format!(
+            "page_size must be between 1 and {}",
+            store::MAX_PAGE_SIZE
+        )));
+    }
+    
+    ensure_templates_loaded(); // Ensure templates are loaded
+    Ok(WISDOM_TEMPLATES.with(|store| {
+        store.borrow().template_page(context.as_deref(), filter.as_deref(), request.cursor, page_size)
+    }))
+}
+
+/// Get the quantum adjectives for a quantum state
+#[ic_cdk::query]
+pub fn get_adjectives_for_state(state: String) -> Result<Vec<WisdomEntry>, KittyError> {
+    let state = validation::catalogue_key("state", &state)?;
     ensure_templates_loaded(); // Ensure templates are loaded
     WISDOM_TEMPLATES.with(|store| {
-        let mut store = store.borrow_mut();
-        let phrases = store.zen_phrases.entry(mood).or_insert_with(Vec::new);
-        
-        if !phrases.contains(&phrase) {
-            phrases.push(phrase);
-        }
+        store.borrow().active_entries(Vocabulary::Adjective, &state)
+            .ok_or_else(|| KittyError::NotFound(format!("No adjectives for quantum state '{}'", state)))
     })
 }
 
-/// Query methods to get templates
+/// Get the zen phrases for a zen mood
 #[ic_cdk::query]
-pub fn get_templates_for_context(context: String) -> Vec<String> {
+pub fn get_phrases_for_mood(mood: String) -> Result<Vec<WisdomEntry>, KittyError> {
+    let mood = validation::catalogue_key("mood", &mood)?;
     ensure_templates_loaded(); // Ensure templates are loaded
     WISDOM_TEMPLATES.with(|store| {
-        store.borrow().templates.get(&context)
-            .cloned()
-            .unwrap_or_default()
+        store.borrow().active_entries(Vocabulary::Phrase, &mood)
+            .ok_or_else(|| KittyError::NotFound(format!("No phrases for zen mood '{}'", mood)))
     })
 }
 
+/// List the slot dictionaries with their word counts
+#[ic_cdk::query]
+pub fn list_slot_dictionaries() -> Vec<store::SlotDictionaryInfo> {
+    ensure_templates_loaded(); // Ensure templates are loaded
+    WISDOM_TEMPLATES.with(|store| store.borrow().dictionary_infos())
+}
+
+/// Get the words in a slot dictionary under a key (or the words for any key if none is given)
+#[ic_cdk::query]
+pub fn get_slot_words(dictionary: String, key: Option<String>) -> Result<Vec<WisdomEntry>, KittyError> {
+    let dictionary = validation::dictionary_name(&dictionary)?;
+    let key = slot_key(&dictionary, key.as_deref())?;
+    WISDOM_TEMPLATES.with(|store| store.borrow().word_entries(&dictionary, &key))
+}
+
+/// Community keywords starting with `prefix`, most trending first
+/// Each comes with the number of templates, adjectives, phrases and words tagged with it,
+/// so the frontend can favour keywords that steer the wisdom.
+#[ic_cdk::query]
+pub fn autocomplete_keywords(prefix: String, limit: Option<u32>) -> Result<Vec<keywords::KeywordInfo>, KittyError> {
+    let prefix = validation::keyword(&prefix)?;
+    let limit = keyword_limit(limit)?;
+    Ok(WISDOM_TEMPLATES.with(|store| {
+        let store = store.borrow();
+        keywords::autocomplete(&prefix, limit, time(), |k| store.tagged_count(k))
+    }))
+}
+
+/// The community keywords used most in recent wisdom requests
+#[ic_cdk::query]
+pub fn trending_keywords(limit: Option<u32>) -> Result<Vec<keywords::KeywordInfo>, KittyError> {
+    let limit = keyword_limit(limit)?;
+    Ok(WISDOM_TEMPLATES.with(|store| {
+        let store = store.borrow();
+        keywords::trending(limit, time(), |k| store.tagged_count(k))
+    }))
+}
+
+/// The keywords the caller used most recently, most recent first
+#[ic_cdk::query]
+pub fn get_recent_keywords() -> Result<Vec<String>, KittyError> {
+    let caller = access::authenticated_caller()?;
+    Ok(keywords::recent(&caller))
+}
+
+/// Remove a community keyword from autocomplete, trending and recent keywords (curators only)
+/// It comes back if someone requests it again.
+#[ic_cdk::update]
+pub fn remove_keyword(keyword: String) -> Result<(), KittyError> {
+    let keyword = validation::keyword(&keyword)?;
+    access::require_role(Role::Curator)?;
+    access::check_rate_limit(&caller())?;
+    keywords::remove(&keyword)
+}
+
+/// Check a keyword result limit, defaulting it if missing
+fn keyword_limit(limit: Option<u32>) -> Result<u32, KittyError> {
+    let limit = limit.unwrap_or(keywords::DEFAULT_KEYWORD_LIMIT);
+    if limit == 0 || limit > keywords::MAX_KEYWORD_LIMIT {
+        return Err(KittyError::InvalidInput(format!(
+            "limit must be between 1 and {}",
+            keywords::MAX_KEYWORD_LIMIT
+        )));
+    }
+    Ok(limit)
+}
+
 /// Ensure templates are loaded
 fn ensure_templates_loaded() {
+    seed_default_templates();
+}
+
+/// Seed the default templates, adjectives and phrases if the store is empty
+/// Returns true if the defaults were installed
+fn seed_default_templates() -> bool {
     WISDOM_TEMPLATES.with(|store| {
-        let store = store.borrow();
-        if store.templates.is_empty() {
-            drop(store); // Release the borrow before calling init
-            init();
+        let mut store = store.borrow_mut();
+        if !store.templates.is_empty() {
+            return false;
         }
-    });
+        *store = default_template_store();
+        true
+    })
 }
 
 /// Generate wisdom based on current state and context
-#[ic_cdk::query]
-pub fn generate_kitty_wisdom(kitty_name: String, contexts: Vec<String>) -> WisdomResponse {
+/// Every context is weighted equally. This is an update call because it records
+/// usage of the chosen template.
+#[ic_cdk::update]
+pub fn generate_kitty_wisdom(kitty_name: String, contexts: Vec<String>) -> Result<WisdomResponse, KittyError> {
+    generate_wisdom(WisdomRequest {
+        kitty_name,
+        contexts: contexts.into_iter().map(|context| WeightedContext { context, weight: 1 }).collect(),
+        recipient_name: None,
+        sender_name: None,
+        relationship: None,
+        recipient_age: None,
+        utc_offset_minutes: None,
+        goal: None,
+        register: None,
+        keywords: Vec::new(),
+        length: None,
+    })
+}
+
+/// Generate wisdom for a request, personalized for its recipient if it has one
+/// and written for its goal, register, keywords and length where templates allow
+#[ic_cdk::update]
+pub fn generate_wisdom(request: WisdomRequest) -> Result<WisdomResponse, KittyError> {
     ensure_templates_loaded(); // Call ensure_templates_loaded before generating wisdom
     
-    // Get current global state
-    let state = GLOBAL_STATE.with(|s| s.borrow().clone());
+    let kitty_name = validation::name("kitty_name", &request.kitty_name)?;
+    let recipient = validate_recipient(&request)?;
+    let preferences = validate_preferences(&request)?;
+    let utc_offset = request.utc_offset_minutes.map(calendar::validate_utc_offset).transpose()?.unwrap_or(0);
+    let now = time();
+    let moment = Moment::at(now, utc_offset);
+    
+    let contexts = resolve_contexts(&request.contexts)?;
+    let seed = random::call_seed(caller(), &candid::encode_one(&request).unwrap_or_default(), now);
     
-    // Use the first context or default to "general"
-    let context = contexts.first().cloned().unwrap_or_else(|| "general".to_string());
+    // Bonded callers see the global state through their personal kitty
+    let member = access::authenticated_caller().ok();
+    let global = GLOBAL_STATE.with(|s| s.borrow().clone());
+    let config = config::current();
+    let state = personal::state_for(member, &global, &config);
     
     // Select wisdom template based on context and state
-    let wisdom = craft_wisdom(&state, &context, Some(&kitty_name));
+    let recent = member.map(|principal| served::recent(&principal)).unwrap_or_default();
+    let crafting = Crafting::new(&state, Some(&kitty_name), &recipient, &preferences, moment, seed);
+    let wisdom = craft_wisdom(crafting, &contexts, &recent);
+    if let Some(id) = wisdom.template_id {
+        WISDOM_TEMPLATES.with(|store| store.borrow_mut().record_usage(Vocabulary::Template, id, time()));
+        if let Some(principal) = member {
+            served::record(principal, id);
+        }
+    }
+    if let Some(principal) = member {
+        personal::record_visit(principal, now, &config);
+    }
+    
+    // Share the keywords with the community pool and the caller's recent keywords;
+    // only signed-in callers within their keyword rate limit contribute
+    if let Some(principal) = member.filter(|p| !preferences.keywords.is_empty() && access::allow_keyword_recording(p)) {
+        keywords::record(principal, &preferences.keywords, time());
+    }
     
-    WisdomResponse {
-        content: wisdom,
+    Ok(WisdomResponse {
+        content: wisdom.content,
         quantum_state: state.quantum_state,
         energy_level: state.energy_level,
         zen_mood: state.zen_mood,
-    }
+        template_id: wisdom.template_id,
+        context: wisdom.context,
+        choices: wisdom.choices,
+        seed,
+        generated_at: now,
+    })
 }
 
-/// Helper function to craft wisdom based on templates
-fn craft_wisdom(state: &KittyState, context: &str, kitty_name: Option<&str>) -> String {
-    // Get templates for the given context, falling back to general if none exist
-    let templates = WISDOM_TEMPLATES.with(|store| {
-        let store = store.borrow();
-        store.templates.get(context)
-            .or_else(|| store.templates.get("general"))
-            .cloned()
-            .unwrap_or_default()
-    });
+/// Render a wisdom again from its original request and the seed, template and kitty
+/// state reported in its response
+///
+/// The result matches the original as long as the template, the adjectives and phrases
+/// for that state and mood, and any slot dictionaries it uses haven't changed since.
+/// Only curators can replay deleted templates. Nothing is recorded.
+#[ic_cdk::query]
+pub fn regenerate_wisdom(replay: WisdomReplay) -> Result<WisdomResponse, KittyError> {
+    ensure_templates_loaded();
     
-    if templates.is_empty() {
-        return "Quantum Kitty is meditating deeply...".to_string();
-    }
+    let request = &replay.request;
+    let kitty_name = validation::name("kitty_name", &request.kitty_name)?;
+    let recipient = validate_recipient(request)?;
+    let preferences = validate_preferences(request)?;
+    let utc_offset = request.utc_offset_minutes.map(calendar::validate_utc_offset).transpose()?.unwrap_or(0);
+    let moment = Moment::at(replay.generated_at, utc_offset);
+    let state = KittyState {
+        quantum_state: validation::sanitize_text("quantum_state", &replay.quantum_state, validation::MAX_KEY_CHARS)?,
+        energy_level: replay.energy_level,
+        zen_mood: validation::sanitize_text("zen_mood", &replay.zen_mood, validation::MAX_KEY_CHARS)?,
+        last_updated: replay.generated_at,
+    };
+    let curator = access::role_of(&caller()).includes(Role::Curator);
+    let (context, template) = WISDOM_TEMPLATES.with(|store| {
+        let store = store.borrow();
+        if curator {
+            store.template(replay.template_id)
+        } else {
+            store.active_template(replay.template_id)
+        }
+    })?;
     
-    // Select template based on energy level and time
-    let now = time();
-    let template_index = ((state.energy_level as u64 + now / 1_000_000_000) as usize) % templates.len();
-    let mut wisdom = templates[template_index].clone();
+    let crafting = Crafting::new(&state, Some(&kitty_name), &recipient, &preferences, moment, replay.seed);
+    let (content, word_keywords) = crafting.render(&template.text, &context);
+    let wisdom = crafting.finish(&template, &context, content, word_keywords);
     
-    // Get quantum adjectives for current state
-    let quantum_adjs = WISDOM_TEMPLATES.with(|store| {
+    Ok(WisdomResponse {
+        content: wisdom.content,
+        quantum_state: state.quantum_state,
+        energy_level: state.energy_level,
+        zen_mood: state.zen_mood,
+        template_id: wisdom.template_id,
+        context: wisdom.context,
+        choices: wisdom.choices,
+        seed: replay.seed,
+        generated_at: replay.generated_at,
+    })
+}
+
+/// Validate the requested contexts and resolve each to one that has templates
+///
+/// A context without servable templates (active, with a weight above 0) is replaced by
+/// the first context along its configured fallback chain that has some; one with no
+/// fallback configured is unknown.
+/// Contexts that can't be resolved are skipped, so the request only fails if none can.
+/// Contexts that resolve to the same place have their weights added together.
+fn resolve_contexts(requested: &[WeightedContext]) -> Result<Vec<(String, u32)>, KittyError> {
+    let config = config::current();
+    if requested.is_empty() {
+        return Ok(vec![(config.default_context.clone(), 1)]);
+    }
+    if requested.len() > MAX_REQUEST_CONTEXTS {
+        return Err(KittyError::InvalidInput(format!(
+            "At most {} contexts may be requested",
+            MAX_REQUEST_CONTEXTS
+        )));
+    }
+    if requested.iter().all(|c| c.weight == 0) {
+        return Err(KittyError::InvalidInput("At least one context must have a weight above zero".to_string()));
+    }
+
+    let mut resolved: Vec<(String, u32)> = Vec::new();
+    let mut unresolved: Option<KittyError> = None;
+    WISDOM_TEMPLATES.with(|store| {
         let store = store.borrow();
-        store.quantum_adjectives.get(&state.quantum_state)
-            .cloned()
-            .unwrap_or_else(|| vec!["quantum".to_string()])
-    });
+        for requested in requested {
+            let context = validation::context_key(&requested.context)?;
+            if requested.weight == 0 {
+                continue;
+            }
+            let has_templates = |c: &String| store.servable_templates(c).is_some();
+            let target = if has_templates(&context) {
+                context
+            } else if config.context_fallbacks.contains_key(&context) {
+                match config.fallback_chain(&context).into_iter().find(has_templates) {
+                    Some(target) => target,
+                    None => {
+                        unresolved.get_or_insert_with(|| {
+                            KittyError::NotFound(format!("No context along the fallback chain of '{}' has templates", context))
+                        });
+                        continue;
+                    }
+                }
+            } else {
+                unresolved.get_or_insert_with(|| KittyError::NotFound(format!("Unknown context '{}'", context)));
+                continue;
+            };
+            match resolved.iter_mut().find(|(c, _)| *c == target) {
+                Some((_, weight)) => *weight = weight.saturating_add(requested.weight),
+                None => resolved.push((target, requested.weight)),
+            }
+        }
+        Ok(())
+    })?;
     
-    // Get zen phrases for current mood
-    let zen_phrases = WISDOM_TEMPLATES.with(|store| {
-        let store = store.borrow();
-        store.zen_phrases.get(&state.zen_mood)
-            .cloned()
-            .unwrap_or_else(|| vec!["The present moment is all we ever truly have.".to_string()])
+    match unresolved {
+        Some(error) if resolved.is_empty() => Err(error),
+        _ => Ok(resolved),
+    }
+}
+
+/// Validate the personalization fields of a wisdom request
+fn validate_recipient(request: &WisdomRequest) -> Result<Recipient, KittyError> {
+    if request.recipient_age.is_some_and(|age| age > MAX_RECIPIENT_AGE) {
+        return Err(KittyError::InvalidInput(format!(
+            "recipient_age must be at most {}",
+            MAX_RECIPIENT_AGE
+        )));
+    }
+    Ok(Recipient {
+        name: request.recipient_name.as_deref().map(|n| validation::name("recipient_name", n)).transpose()?,
+        sender: request.sender_name.as_deref().map(|n| validation::name("sender_name", n)).transpose()?,
+        relationship: request.relationship.as_deref().map(|r| validation::name("relationship", r)).transpose()?,
+        age: request.recipient_age,
+    })
+}
+
+/// Validate the style preferences of a wisdom request
+fn validate_preferences(request: &WisdomRequest) -> Result<Preferences, KittyError> {
+    if request.keywords.len() > MAX_REQUEST_KEYWORDS {
+        return Err(KittyError::InvalidInput(format!(
+            "At most {} keywords may be requested",
+            MAX_REQUEST_KEYWORDS
+        )));
+    }
+    let mut keywords: Vec<String> = Vec::with_capacity(request.keywords.len());
+    for keyword in &request.keywords {
+        let keyword = validation::keyword(keyword)?;
+        if !keywords.contains(&keyword) {
+            keywords.push(keyword);
+        }
+    }
+    Ok(Preferences {
+        goal: request.goal,
+        register: request.register,
+        length: request.length,
+        keywords,
+    })
+}
+
+/// Crafting is everything a wisdom is rendered from apart from its template
+///
+/// The adjective, the phrase and the generator for alternatives and words are drawn
+/// from the seed first, in that order, so a wisdom can be rendered again exactly from
+/// its seed and template ID (see `regenerate_wisdom`).
+struct Crafting<'a> {
+    state: &'a KittyState,
+    kitty: &'a str,
+    recipient: &'a Recipient,
+    preferences: &'a Preferences,
+    moment: Moment,
+    quantum: (String, Vec<String>), // Chosen quantum adjective and the keywords it honors
+    zen: (String, Vec<String>),     // Chosen zen phrase and the keywords it honors
+    render_rng: Rng,                // Starting point for every render's alternatives and words
+    rng: Rng,                       // Draws the context and template
+}
+
+impl<'a> Crafting<'a> {
+    fn new(
+        state: &'a KittyState,
+        kitty_name: Option<&'a str>,
+        recipient: &'a Recipient,
+        preferences: &'a Preferences,
+        moment: Moment,
+        seed: u64,
+    ) -> Self {
+        let mut rng = Rng::from_seed(seed);
+        
+        // Select an adjective for the current state and a phrase for the current mood,
+        // preferring those tagged with the requested keywords, then at random
+        let quantum = WISDOM_TEMPLATES.with(|store| {
+            let adjectives = store.borrow().active_entries(Vocabulary::Adjective, &state.quantum_state).unwrap_or_default();
+            pick_slot_entry(&adjectives, preferences, &mut rng)
+        }).unwrap_or_else(|| ("quantum".to_string(), Vec::new()));
+        let zen = WISDOM_TEMPLATES.with(|store| {
+            let phrases = store.borrow().active_entries(Vocabulary::Phrase, &state.zen_mood).unwrap_or_default();
+            pick_slot_entry(&phrases, preferences, &mut rng)
+        }).unwrap_or_else(|| ("The present moment is all we ever truly have.".to_string(), Vec::new()));
+        let render_rng = Rng::from_seed(rng.next_u64());
+        
+        Self {
+            state,
+            // Use provided kitty name or default
+            kitty: kitty_name.unwrap_or("Quantum Kitty"),
+            recipient,
+            preferences,
+            moment,
+            quantum,
+            zen,
+            render_rng,
+            rng,
+        }
+    }
+
+    /// Fill in placeholders and resolve sections in a single pass, then fix articles,
+    /// capitalization and spacing left by substitution. Also returns the keywords
+    /// honored by the slot dictionary words used. Every template renders with the
+    /// same choices for alternatives and words.
+    fn render(&self, text: &str, context: &str) -> (String, Vec<String>) {
+        WISDOM_TEMPLATES.with(|store| {
+            let store = store.borrow();
+            let mut ctx = WisdomContext {
+                state: self.state,
+                store: &store,
+                context,
+                quantum: self.quantum.0.clone(),
+                zen: self.zen.0.clone(),
+                kitty: self.kitty,
+                recipient: self.recipient,
+                preferences: self.preferences,
+                moment: self.moment,
+                rng: self.render_rng.clone(),
+                honored: Vec::new(),
+            };
+            let wisdom = grammar::polish(&Template::parse_lenient(text).render(&mut ctx));
+            (wisdom, ctx.honored)
+        })
+    }
+
+    /// The crafted wisdom for `template` in `context`, rendered as `wisdom` honoring `word_keywords`
+    fn finish(&self, template: &WisdomEntry, context: &str, wisdom: String, word_keywords: Vec<String>) -> CraftedWisdom {
+        let fit = self.preferences.fit(&template.tags);
+        let length = Length::of(&wisdom);
+        let honored: Vec<String> = [fit.keywords, self.quantum.1.clone(), self.zen.1.clone(), word_keywords].concat();
+        
+        CraftedWisdom {
+            template_id: Some(template.id),
+            context: context.to_string(),
+            choices: WisdomChoices {
+                goal_honored: fit.goal,
+                register_honored: fit.register,
+                keywords_honored: self.preferences.honored_keywords(&honored),
+                length,
+                length_honored: self.preferences.length == Some(length),
+            },
+            content: wisdom,
+        }
+    }
+}
+
+/// Helper function to craft wisdom based on templates
+/// `contexts` are weighted contexts, as resolved by `resolve_contexts`, and `recent`
+/// the templates the caller was served recently (most recent first).
+fn craft_wisdom(mut crafting: Crafting, contexts: &[(String, u32)], recent: &[u64]) -> CraftedWisdom {
+    // Draw a context in proportion to its weight
+    let weights: Vec<u64> = contexts.iter().map(|(_, weight)| u64::from(*weight)).collect();
+    let context = crafting
+        .rng
+        .weighted(&weights)
+        .and_then(|index| contexts.get(index))
+        .or(contexts.first())
+        .map_or("general", |(context, _)| context.as_str());
+
+    let templates = WISDOM_TEMPLATES.with(|store| {
+        store.borrow().servable_templates(context).unwrap_or_default()
     });
     
-    // Select adjective and phrase based on time to create variety
-    let adj_index = (now as usize / 1_000_000_000) % quantum_adjs.len();
-    let phrase_index = (now as usize / 1_000_000_000) % zen_phrases.len();
-    
-    // Fill in template placeholders
-    wisdom = wisdom.replace("{quantum}", &quantum_adjs[adj_index]);
-    wisdom = wisdom.replace("{zen}", &zen_phrases[phrase_index]);
-    wisdom = wisdom.replace("{name}", "you");
+    if templates.is_empty() {
+        let content = "Quantum Kitty is meditating deeply...".to_string();
+        return CraftedWisdom {
+            template_id: None,
+            context: context.to_string(),
+            choices: WisdomChoices::unstyled(&content),
+            content,
+        };
+    }
     
-    // Use provided kitty name or default
-    let kitty = kitty_name.unwrap_or("Quantum Kitty");
-    wisdom = wisdom.replace("{kitty}", kitty);
+    // Keep the templates that best fit the requested goal, register and keywords
+    let preferences = crafting.preferences;
+    let best = templates.iter().map(|t| preferences.fit(&t.tags).score()).max();
+    let fitting: Vec<&WisdomEntry> = templates
+        .iter()
+        .filter(|t| Some(preferences.fit(&t.tags).score()) == best)
+        .collect();
     
-    wisdom
+    // Select among them by weight, avoiding what the caller has seen recently. Length
+    // is judged on the rendered wisdom, so only a length preference renders them all.
+    let (template, (wisdom, word_keywords)) = match preferences.length {
+        Some(length) => {
+            let mut candidates: Vec<(&WisdomEntry, (String, Vec<String>))> =
+                fitting.iter().map(|&t| (t, crafting.render(&t.text, context))).collect();
+            if candidates.iter().any(|(_, (wisdom, _))| Length::of(wisdom) == length) {
+                candidates.retain(|(_, (wisdom, _))| Length::of(wisdom) == length);
+            }
+            let candidate_templates: Vec<&WisdomEntry> = candidates.iter().map(|(t, _)| *t).collect();
+            let template_index = pick_template(&candidate_templates, recent, &mut crafting.rng);
+            candidates.swap_remove(template_index)
+        }
+        None => {
+            let template = fitting[pick_template(&fitting, recent, &mut crafting.rng)];
+            (template, crafting.render(&template.text, context))
+        }
+    };
+    crafting.finish(template, context, wisdom, word_keywords)
+}
+
+/// Index of the template to serve from `candidates` (which must not be empty)
+///
+/// Candidates must all be servable (weight above 0). Those not in `recent` are drawn in
+/// proportion to their weight; if every candidate was served recently, the one served
+/// longest ago is used.
+fn pick_template(candidates: &[&WisdomEntry], recent: &[u64], rng: &mut Rng) -> usize {
+    let fresh: Vec<usize> = (0..candidates.len()).filter(|&i| !recent.contains(&candidates[i].id)).collect();
+    if fresh.is_empty() {
+        let age = |i: &usize| recent.iter().position(|&id| id == candidates[*i].id);
+        return (0..candidates.len()).max_by_key(age).unwrap_or(0);
+    }
+    let weights: Vec<u64> = fresh.iter().map(|&i| u64::from(candidates[i].weight)).collect();
+    let pick = rng.weighted(&weights).unwrap_or(0);
+    fresh[pick]
 }
 
+/// Choose an adjective or phrase from `entries`: those tagged with the most requested
+/// keywords first, then at random. Returns its text and the keywords it honors, or
+/// None if there are no entries.
+fn pick_slot_entry(entries: &[WisdomEntry], preferences: &Preferences, rng: &mut Rng) -> Option<(String, Vec<String>)> {
+    let best = preferences.best_keyword_matches(entries);
+    if best.is_empty() {
+        return None;
+    }
+    let entry = best[rng.below(best.len())];
+    Some((entry.text.clone(), preferences.fit(&entry.tags).keywords))
+}
 /// Initialize the canister with default templates and phrases
+/// 
+/// An optional KittyConfig overrides the default state/mood catalogue and rotation periods.
 #[ic_cdk::init]
-pub fn init() {
-    let mut store = WisdomTemplateStore::default();
-    
-    // Add default general templates
-    let general_templates = vec![
-        "{kitty} observes your presence with {quantum} awareness. The path reveals itself one paw print at a time. {zen}".to_string(),
-        "In the space between thoughts, {kitty} finds infinite {quantum} possibilities. Your journey continues to unfold beautifully. {zen}".to_string(),
-        "The {quantum} observer changes what is observed. {kitty} sees your potential across multiple dimensions. {zen}".to_string(),
-        "When you pet {kitty}, ripples of {quantum} energy spread throughout the universe. {zen}".to_string(),
-        "Time folds like origami in {kitty}'s {quantum} perception. Your now contains all possible futures. {zen}".to_string(),
-    ];
-    store.templates.insert("general".to_string(), general_templates);
-    
-    // Add default birthday templates
-    let birthday_templates = vec![
-        "As {name} completes another orbit around the sun, {kitty} sees the {quantum} possibilities unfolding in your path. {zen}".to_string(),
-        "Time is but an illusion when measured in {quantum} joy. {kitty} celebrates your special day with purrs that transcend dimensions. {zen}".to_string(),
-        "Your birthday creates a {quantum} resonance that {kitty} feels across all timelines. May your new cycle bring enlightenment. {zen}".to_string(),
-    ];
-    store.templates.insert("birthday".to_string(), birthday_templates);
-    
-    // Add default team templates
-    let team_templates = vec![
-        "When minds synchronize in {quantum} harmony, your team creates ripples across the universe. {kitty} observes your collective potential. {zen}".to_string(),
-        "Like particles in {quantum} entanglement, your team's energy affects outcomes beyond what you can see. {kitty} sends wisdom for your collaboration. {zen}".to_string(),
-        "Your team exists in a {quantum} field of shared consciousness. {kitty} sees how your collective intention shapes reality. {zen}".to_string(),
-    ];
-    store.templates.insert("team".to_string(), team_templates);
-    
-    // Add reunion templates for returning users
-    let reunion_templates = vec![
-        "The {quantum} bond between you and {kitty} resonates across spacetime. Your return was anticipated in multiple dimensions. {zen}".to_string(),
-        "{kitty} recognizes your quantum signature instantly. The dimensional fold between you has strengthened with time. {zen}".to_string(),
-        "Time is an illusion in the {quantum} field where you and {kitty} exist. Your connection transcends conventional reality. {zen}".to_string(),
-        "Your return creates ripples in {kitty}'s {quantum} awareness. The resonance between you grows stronger with each reconnection. {zen}".to_string(),
-    ];
-    store.templates.insert("reunion".to_string(), reunion_templates);
+pub fn init(kitty_config: Option<config::KittyConfig>) {
+    apply_config_arg(kitty_config);
+    seed_default_templates();
     
-    // Add bonding templates for new bonds
-    let bonding_templates = vec![
-        "A new {quantum} bond forms between you and {kitty}, creating resonance patterns that will echo through dimensions. {zen}".to_string(),
-        "As you name {kitty}, a {quantum} connection crystallizes. This bond will persist across space and time. {zen}".to_string(),
-        "{kitty} feels your intention and responds with {quantum} recognition. The universe acknowledges this new connection. {zen}".to_string(),
-        "The act of naming creates a {quantum} bridge between consciousnesses. {kitty} now exists in resonance with you. {zen}".to_string(),
-    ];
-    store.templates.insert("bonding".to_string(), bonding_templates);
+    // Initialize the global state and keep it pulsing
+    pulse_kitty_state();
+    pulse::start_pulse_timer();
+    random::start_reseed_timer();
+}
+
+/// Goal and register tags of the default templates in each context
+pub(crate) const DEFAULT_TEMPLATE_TAGS: [(&str, &[&str]); 5] = [
+    ("general", &["motivation", "friendly"]),
+    ("birthday", &["birthday-present", "celebration", "friendly"]),
+    ("team", &["meeting-opener", "motivation", "corporate"]),
+    ("reunion", &["gratitude", "friendly"]),
+    ("bonding", &["celebration", "friendly"]),
+];
+
+/// Build the default template store shipped with the canister
+fn default_template_store() -> WisdomTemplateStore {
+    let templates = default_templates();
     
     // Add default quantum adjectives
     let mut quantum_adjectives = HashMap::new();
@@ -424,7 +1366,6 @@ pub fn init() {
         "origami-like".to_string(),
     ]);
     
-    store.quantum_adjectives = quantum_adjectives;
     
     // Add default zen phrases
     let mut zen_phrases = HashMap::new();
@@ -464,38 +1405,341 @@ pub fn init() {
         "The awakened mind sees no separation between self and other.".to_string(),
     ]);
     
-    store.zen_phrases = zen_phrases;
+    let now = time();
+    let mut store = WisdomTemplateStore::from_text(templates, quantum_adjectives, zen_phrases, now);
     
-    WISDOM_TEMPLATES.with(|s| {
-        *s.borrow_mut() = store;
-    });
+    // Tag the defaults with the goals and registers they suit
+    for (context, tags) in DEFAULT_TEMPLATE_TAGS {
+        for entry in store.active_entries(Vocabulary::Template, context).unwrap_or_default() {
+            let tags = tags.iter().map(|tag| tag.to_string()).collect();
+            store.set_tags(Vocabulary::Template, entry.id, tags, now).expect("default template exists");
+        }
+    }
     
-    // Initialize the global state
-    update_kitty_state();
+    store
 }
 
-/// Post-upgrade hook to ensure templates are initialized after canister upgrades
+/// The wisdom templates shipped with the canister, by context
+pub(crate) fn default_templates() -> HashMap<String, Vec<String>> {
+    let mut templates = HashMap::new();
+    
+    // Add default general templates
+    let general_templates = vec![
+        "{kitty} observes your presence with {quantum} awareness. The path reveals itself one paw print at a time. {zen}".to_string(),
+        "In the space between thoughts, {kitty} finds infinite {quantum} possibilities. Your journey continues to unfold beautifully. {zen}".to_string(),
+        "The {quantum} observer changes what is observed. {kitty} sees your potential across multiple dimensions. {zen}".to_string(),
+        "When you pet {kitty}, ripples of {quantum} energy spread throughout the universe. {zen}".to_string(),
+        "Time folds like origami in {kitty}'s {quantum} perception. Your now contains all possible futures. {zen}".to_string(),
+    ];
+    templates.insert("general".to_string(), general_templates);
+    
+    // Add default birthday templates
+    let birthday_templates = vec![
+        "As {name} {#if recipient}completes{#else}complete{/if} {#if age}{their} {age_ordinal}{#else}another{/if} orbit around the sun, {kitty} sees the {quantum} possibilities unfolding in {their} path. {zen}".to_string(),
+        "Time is but an illusion when measured in {quantum} joy. {kitty} celebrates {their} special day with purrs that transcend dimensions. {zen}".to_string(),
+        "{#if recipient}{name}'s{#else}Your{/if} birthday creates a {quantum} resonance that {kitty} feels across all timelines. May {their} new cycle bring enlightenment. {zen}".to_string(),
+    ];
+    templates.insert("birthday".to_string(), birthday_templates);
+    
+    // Add default team templates
+    let team_templates = vec![
+        "When minds synchronize in {quantum} harmony, your team creates ripples across the universe. {kitty} observes your collective potential. {zen}".to_string(),
+        "Like particles in {quantum} entanglement, your team's energy affects outcomes beyond what you can see. {kitty} sends wisdom for your collaboration. {zen}".to_string(),
+        "Your team exists in a {quantum} field of shared consciousness. {kitty} sees how your collective intention shapes reality. {zen}".to_string(),
+        "As your team gathers this {weekday} {time_of_day}, {kitty} feels a {quantum} alignment of purpose.{#if weekday=Monday} A fresh week unfolds in superposition.{/if} {zen}".to_string(),
+    ];
+    templates.insert("team".to_string(), team_templates);
+    
+    // Add reunion templates for returning users
+    let reunion_templates = vec![
+        "The {quantum} bond between you and {kitty} resonates across spacetime. Your return was anticipated in multiple dimensions. {zen}".to_string(),
+        "{kitty} recognizes your quantum signature instantly. The dimensional fold between you has strengthened with time. {zen}".to_string(),
+        "Time is an illusion in the {quantum} field where you and {kitty} exist. Your connection transcends conventional reality. {zen}".to_string(),
+        "Your return creates ripples in {kitty}'s {quantum} awareness. The resonance between you grows stronger with each reconnection. {zen}".to_string(),
+    ];
+    templates.insert("reunion".to_string(), reunion_templates);
+    
+    // Add bonding templates for new bonds
+    let bonding_templates = vec![
+        "A new {quantum} bond forms between you and {kitty}, creating resonance patterns that will echo through dimensions. {zen}".to_string(),
+        "As you name {kitty}, a {quantum} connection crystallizes. This bond will persist across space and time. {zen}".to_string(),
+        "{kitty} feels your intention and responds with {quantum} recognition. The universe acknowledges this new connection. {zen}".to_string(),
+        "The act of naming creates a {quantum} bridge between consciousnesses. {kitty} now exists in resonance with you. {zen}".to_string(),
+    ];
+    templates.insert("bonding".to_string(), bonding_templates);
+    
+    templates
+}
+
+/// Pre-upgrade hook that writes the kitty state, templates and bonds to stable memory
+#[ic_cdk::pre_upgrade]
+pub fn pre_upgrade() {
+    persistence::save_to_stable_memory();
+}
+
+/// Post-upgrade hook that restores the persisted state after canister upgrades
+/// 
+/// Defaults are only seeded when nothing was persisted (e.g. upgrading from a
+/// version without a pre-upgrade hook), so user-authored content survives deploys.
+/// A KittyConfig upgrade argument replaces the persisted configuration.
 #[ic_cdk::post_upgrade]
-pub fn post_upgrade() {
-    init();
+pub fn post_upgrade(kitty_config: Option<config::KittyConfig>) {
+    if persistence::restore_from_stable_memory() {
+        apply_config_arg(kitty_config);
+        seed_default_templates();
+        pulse_kitty_state();
+        pulse::start_pulse_timer();
+        random::start_reseed_timer();
+    } else {
+        init(kitty_config);
+    }
+}
+
+/// Install a configuration passed as an init/upgrade argument
+/// Traps on invalid configuration so a bad deploy fails instead of running misconfigured
+fn apply_config_arg(kitty_config: Option<config::KittyConfig>) {
+    if let Some(kitty_config) = kitty_config {
+        if let Err(e) = config::apply(kitty_config) {
+            ic_cdk::trap(&format!("{:?}", e));
+        }
+    }
+}
+
+/// Session describes who is calling: a logged-out guest or an authenticated member
+#[derive(CandidType, Deserialize)]
+pub enum Session {
+    Guest,                 // Anonymous caller; per-user features are unavailable
+    Member(MemberSession), // Authenticated caller
+}
+
+/// MemberSession holds what the frontend needs to know about an authenticated caller
+#[derive(CandidType, Deserialize)]
+pub struct MemberSession {
+    principal: Principal,       // The caller's principal ID
+    role: Role,                 // The caller's effective role
+    kitty_name: Option<String>, // The bonded kitty name, if any
+}
+
+/// Describe the calling session
+/// Logged-out visitors get an explicit Guest response instead of shared per-user state
+#[ic_cdk::query]
+pub fn whoami() -> Session {
+    match access::authenticated_caller() {
+        Ok(principal) => Session::Member(MemberSession {
+            principal,
+            role: access::role_of(&principal),
+            kitty_name: USER_KITTY_BONDS.with(|bonds| bonds.borrow().get(&principal).cloned()),
+        }),
+        Err(_) => Session::Guest,
+    }
 }
 
 /// Save a kitty name associated with the caller's principal ID
 /// This creates a persistent bond between the user and their quantum kitty
 #[ic_cdk::update]
-pub fn save_kitty_name(name: String) {
-    let caller = caller();
+pub fn save_kitty_name(name: String) -> Result<(), KittyError> {
+    let caller = access::authenticated_caller()?;
+    access::check_rate_limit(&caller)?;
+    let name = validation::name("name", &name)?;
     USER_KITTY_BONDS.with(|bonds| {
         bonds.borrow_mut().insert(caller, name);
     });
+    personal::bond(caller);
+    Ok(())
 }
 
 /// Retrieve the kitty name associated with the caller's principal ID
 /// Returns None if the user hasn't named their kitty yet
 #[ic_cdk::query]
-pub fn get_kitty_name() -> Option<String> {
-    let caller = caller();
-    USER_KITTY_BONDS.with(|bonds| {
+pub fn get_kitty_name() -> Result<Option<String>, KittyError> {
+    let caller = access::authenticated_caller()?;
+    Ok(USER_KITTY_BONDS.with(|bonds| {
         bonds.borrow().get(&caller).cloned()
-    })
+    }))
+}
+
+/// Retrieve the caller's personal kitty and the state it shows them
+/// Returns None if the user hasn't named their kitty yet
+#[ic_cdk::query]
+pub fn get_personal_kitty() -> Result<Option<personal::PersonalKittyView>, KittyError> {
+    let caller = access::authenticated_caller()?;
+    let global = GLOBAL_STATE.with(|s| s.borrow().clone());
+    Ok(personal::view(&caller, &global, &config::current()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Servable templates with IDs 0.. and the given weights
+    fn entries(weights: &[u32]) -> Vec<WisdomEntry> {
+        let texts = [("general".to_string(), (0..weights.len()).map(|i| format!("t{}", i)).collect())].into();
+        let mut store = WisdomTemplateStore::from_text(texts, HashMap::new(), HashMap::new(), 0);
+        for (id, &weight) in weights.iter().enumerate() {
+            store.set_weight(id as u64, weight, 0).unwrap();
+        }
+        store.active_entries(Vocabulary::Template, "general").unwrap()
+    }
+
+    #[test]
+    fn pick_template_skips_recently_served_templates() {
+        let entries = entries(&[1, 1, 1]);
+        let candidates: Vec<&WisdomEntry> = entries.iter().collect();
+        let mut rng = Rng::from_seed(7);
+        for _ in 0..100 {
+            assert_eq!(pick_template(&candidates, &[0, 2], &mut rng), 1);
+        }
+    }
+
+    #[test]
+    fn pick_template_draws_fresh_templates_by_weight() {
+        let entries = entries(&[1, 3]);
+        let candidates: Vec<&WisdomEntry> = entries.iter().collect();
+        let mut rng = Rng::from_seed(7);
+        let mut picks = [0; 2];
+        for _ in 0..4000 {
+            picks[pick_template(&candidates, &[], &mut rng)] += 1;
+        }
+        assert!((2700..3300).contains(&picks[1]), "{:?}", picks);
+    }
+
+    #[test]
+    fn pick_template_falls_back_to_the_one_served_longest_ago() {
+        let entries = entries(&[1, 1, 1]);
+        let candidates: Vec<&WisdomEntry> = entries.iter().collect();
+        let mut rng = Rng::from_seed(7);
+        assert_eq!(pick_template(&candidates, &[2, 0, 1], &mut rng), 1);
+        assert_eq!(pick_template(&candidates, &[1, 2, 0], &mut rng), 0);
+    }
+
+    #[test]
+    fn the_kitty_state_rotates_with_the_configured_periods() {
+        let config = config::KittyConfig::default();
+        let start = kitty_state_at(0, &config);
+        assert_eq!(start.quantum_state, config.quantum_states[0]);
+        assert_eq!(start.zen_mood, config.zen_moods[0]);
+        assert_eq!(start.energy_level, config.energy_min);
+
+        let secs = |s: u64| s * 1_000_000_000;
+        let later = kitty_state_at(secs(config.state_period_secs), &config);
+        assert_eq!(later.quantum_state, config.quantum_states[1]);
+        assert_eq!(later.last_updated, secs(config.state_period_secs));
+
+        let cycles = u64::from(config.energy_max - config.energy_min) + 1;
+        let wrapped = kitty_state_at(secs(config.energy_period_secs * cycles), &config);
+        assert_eq!(wrapped.energy_level, config.energy_min);
+        let top = kitty_state_at(secs(config.energy_period_secs * (cycles - 1)), &config);
+        assert_eq!(top.energy_level, config.energy_max);
+
+        let moods = config.zen_moods.len() as u64;
+        let mood_cycle = kitty_state_at(secs(config.mood_period_secs * moods), &config);
+        assert_eq!(mood_cycle.zen_mood, config.zen_moods[0]);
+    }
+
+    /// Install templates in "general" and "team", a "birthday" template that can't be
+    /// served, and fallbacks office -> standup -> team and party -> birthday
+    fn install_contexts() {
+        let texts = [("general", "g"), ("team", "t"), ("birthday", "b")]
+            .into_iter()
+            .map(|(context, text)| (context.to_string(), vec![text.to_string()]))
+            .collect();
+        let mut store = WisdomTemplateStore::from_text(texts, HashMap::new(), HashMap::new(), 0);
+        let birthday = store.active_entries(Vocabulary::Template, "birthday").unwrap()[0].id;
+        store.set_weight(birthday, 0, 0).unwrap();
+        WISDOM_TEMPLATES.with(|s| *s.borrow_mut() = store);
+
+        let fallbacks = [("office", "standup"), ("standup", "team"), ("party", "birthday")];
+        config::apply(config::KittyConfig {
+            context_fallbacks: fallbacks.iter().map(|(c, f)| (c.to_string(), f.to_string())).collect(),
+            ..config::KittyConfig::default()
+        })
+        .unwrap();
+    }
+
+    fn contexts(requested: &[(&str, u32)]) -> Vec<WeightedContext> {
+        requested.iter().map(|&(context, weight)| WeightedContext { context: context.to_string(), weight }).collect()
+    }
+
+    fn resolved(requested: &[(&str, u32)]) -> Vec<(String, u32)> {
+        resolve_contexts(&contexts(requested)).unwrap()
+    }
+
+    #[test]
+    fn no_requested_context_means_the_default_one() {
+        install_contexts();
+        assert_eq!(resolved(&[]), [("general".to_string(), 1)]);
+    }
+
+    #[test]
+    fn contexts_fall_back_along_their_chain_before_the_default() {
+        install_contexts();
+        assert_eq!(resolved(&[("office", 1)]), [("team".to_string(), 1)]);
+        // birthday has no servable template, so the chain ends at the default context
+        assert_eq!(resolved(&[("party", 1)]), [("general".to_string(), 1)]);
+        assert_eq!(resolved(&[("Team", 2), ("office", 3), ("general", 1)]), [("team".to_string(), 5), ("general".to_string(), 1)]);
+    }
+
+    #[test]
+    fn unresolvable_contexts_are_skipped_unless_none_resolve() {
+        install_contexts();
+        assert_eq!(resolved(&[("unknown", 4), ("team", 2), ("general", 0)]), [("team".to_string(), 2)]);
+        assert!(matches!(resolve_contexts(&contexts(&[("unknown", 1)])), Err(KittyError::NotFound(_))));
+
+        // Without templates anywhere along its chain, a context with fallbacks can't resolve either
+        WISDOM_TEMPLATES.with(|s| *s.borrow_mut() = WisdomTemplateStore::default());
+        assert!(matches!(resolve_contexts(&contexts(&[("party", 1)])), Err(KittyError::NotFound(_))));
+    }
+
+    #[test]
+    fn requested_contexts_are_validated() {
+        install_contexts();
+        let all_zero = resolve_contexts(&contexts(&[("team", 0), ("general", 0)]));
+        assert!(matches!(all_zero, Err(KittyError::InvalidInput(_))));
+        let too_many = vec![("team", 1); MAX_REQUEST_CONTEXTS + 1];
+        assert!(matches!(resolve_contexts(&contexts(&too_many)), Err(KittyError::InvalidInput(_))));
+        assert!(matches!(resolve_contexts(&contexts(&[("te am", 1)])), Err(KittyError::InvalidInput(_))));
+    }
+
+    fn request() -> WisdomRequest {
+        WisdomRequest {
+            kitty_name: "Whiskers".to_string(),
+            contexts: Vec::new(),
+            recipient_name: None,
+            sender_name: None,
+            relationship: None,
+            recipient_age: None,
+            utc_offset_minutes: None,
+            goal: None,
+            register: None,
+            keywords: Vec::new(),
+            length: None,
+        }
+    }
+
+    #[test]
+    fn recipient_details_are_cleaned_and_optional() {
+        let recipient = validate_recipient(&request()).unwrap();
+        assert_eq!((recipient.name, recipient.sender, recipient.relationship, recipient.age), (None, None, None, None));
+
+        let recipient = validate_recipient(&WisdomRequest {
+            recipient_name: Some("  Ada\u{200B}  Lovelace ".to_string()),
+            sender_name: Some("Charles".to_string()),
+            relationship: Some("friend".to_string()),
+            recipient_age: Some(MAX_RECIPIENT_AGE),
+            ..request()
+        })
+        .unwrap();
+        assert_eq!(recipient.name.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(recipient.sender.as_deref(), Some("Charles"));
+        assert_eq!(recipient.relationship.as_deref(), Some("friend"));
+        assert_eq!(recipient.age, Some(MAX_RECIPIENT_AGE));
+    }
+
+    #[test]
+    fn invalid_recipient_details_are_rejected() {
+        let too_old = WisdomRequest { recipient_age: Some(MAX_RECIPIENT_AGE + 1), ..request() };
+        assert!(matches!(validate_recipient(&too_old), Err(KittyError::InvalidInput(_))));
+        let blank_sender = WisdomRequest { sender_name: Some("   ".to_string()), ..request() };
+        assert!(matches!(validate_recipient(&blank_sender), Err(KittyError::InvalidInput(_))));
+    }
 }
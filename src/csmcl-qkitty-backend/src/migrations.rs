@@ -0,0 +1,292 @@
+// Schema migrations for persisted canister state
+//
+// Each step decodes the payload written under schema vN and re-encodes it as
+// schema vN+1. `migrate` runs the chain until the payload matches the current
+// `PersistedState`. When a persisted type changes shape:
+//   1. freeze the current layout below as `vN` (copy the structs, don't reuse them)
+//   2. bump `SCHEMA_VERSION` in persistence.rs
+//   3. add a `migrate_vN_to_vN+1` step and register it in `migration_step`
+use candid::CandidType;
+use serde::de::DeserializeOwned;
+
+use crate::persistence::{PersistedState, SCHEMA_VERSION};
+use crate::personal::PersonalKitty;
+use crate::store::{WisdomEntry, DEFAULT_WEIGHT};
+use crate::{default_templates, KittyState, WisdomTemplateStore, DEFAULT_TEMPLATE_TAGS};
+
+/// A single migration step from one schema version to the next
+type MigrationStep = fn(&[u8]) -> Result<Vec<u8>, String>;
+
+/// Schema v1: the layout written by the first upgrade-safe release (no version header)
+mod v1 {
+    use candid::{CandidType, Deserialize, Principal};
+    use std::collections::HashMap;
+
+    #[derive(CandidType, Deserialize)]
+    pub struct KittyState {
+        pub quantum_state: String,
+        pub energy_level: u8,
+        pub zen_mood: String,
+        pub last_updated: u64,
+    }
+
+    #[derive(CandidType, Deserialize)]
+    pub struct WisdomTemplateStore {
+        pub templates: HashMap<String, Vec<String>>,
+        pub quantum_adjectives: HashMap<String, Vec<String>>,
+        pub zen_phrases: HashMap<String, Vec<String>>,
+    }
+
+    #[derive(CandidType, Deserialize)]
+    pub struct PersistedState {
+        pub kitty_state: KittyState,
+        pub templates: WisdomTemplateStore,
+        pub bonds: HashMap<Principal, String>,
+    }
+}
+
+/// Bring a payload written under `version` up to the current schema
+/// Fails if the payload is from a newer wasm or any step can't decode its input
+pub fn migrate(version: u32, payload: &[u8]) -> Result<PersistedState, String> {
+    if version == 0 || version > SCHEMA_VERSION {
+        return Err(format!(
+            "Unsupported schema version {} (this wasm understands up to v{})",
+            version, SCHEMA_VERSION
+        ));
+    }
+
+    let mut current = version;
+    let mut bytes = payload.to_vec();
+    while current < SCHEMA_VERSION {
+        let step = migration_step(current)
+            .ok_or_else(|| format!("No migration registered from schema v{}", current))?;
+        bytes = step(&bytes).map_err(|e| format!("Migration v{} -> v{} failed: {}", current, current + 1, e))?;
+        current += 1;
+    }
+
+    decode(&bytes)
+}
+
+/// Look up the step that upgrades a payload from `version` to `version + 1`
+fn migration_step(version: u32) -> Option<MigrationStep> {
+    match version {
+        1 => Some(migrate_v1_to_v2),
+        _ => None,
+    }
+}
+
+/// v1 -> v2: the current layout, with everything added since v1 starting from defaults
+///   - templates, adjectives and phrases become entries with stable IDs, assigned in
+///     order, section by section, with keys sorted so the result is deterministic
+///   - built-in templates get the goal and register tags a fresh store is seeded with;
+///     a template counts as built-in if its text is exactly one the canister ships for
+///     its context, so curator templates stay untagged
+///   - every bonded user starts with a personal kitty that matches the global kitty
+fn migrate_v1_to_v2(payload: &[u8]) -> Result<Vec<u8>, String> {
+    let old: v1::PersistedState = decode(payload)?;
+
+    let mut templates = WisdomTemplateStore::default();
+    let mut next_id = 0;
+    for (section, texts_by_key) in [
+        (&mut templates.templates, old.templates.templates),
+        (&mut templates.quantum_adjectives, old.templates.quantum_adjectives),
+        (&mut templates.zen_phrases, old.templates.zen_phrases),
+    ] {
+        let mut texts_by_key: Vec<_> = texts_by_key.into_iter().collect();
+        texts_by_key.sort_by(|a, b| a.0.cmp(&b.0));
+        for (key, texts) in texts_by_key {
+            let entries = texts
+                .into_iter()
+                .map(|text| {
+                    let id = next_id;
+                    next_id += 1;
+                    WisdomEntry {
+                        id,
+                        text,
+                        tags: Vec::new(),
+                        weight: DEFAULT_WEIGHT,
+                        author: None,
+                        created_at: 0,
+                        updated_at: 0,
+                        usage_count: 0,
+                        last_used_at: None,
+                        deleted_at: None,
+                    }
+                })
+                .collect();
+            section.insert(key, entries);
+        }
+    }
+    templates.next_id = next_id;
+
+    let built_in = default_templates();
+    for (context, tags) in DEFAULT_TEMPLATE_TAGS {
+        let texts = built_in.get(context).map(Vec::as_slice).unwrap_or_default();
+        let entries = templates.templates.get_mut(context).into_iter().flatten();
+        for entry in entries.filter(|e| texts.contains(&e.text)) {
+            entry.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        }
+    }
+
+    let personal = old.bonds.keys().map(|&principal| (principal, PersonalKitty::default())).collect();
+    let new = PersistedState {
+        kitty_state: KittyState {
+            quantum_state: old.kitty_state.quantum_state,
+            energy_level: old.kitty_state.energy_level,
+            zen_mood: old.kitty_state.zen_mood,
+            last_updated: old.kitty_state.last_updated,
+        },
+        templates,
+        bonds: old.bonds,
+        personal,
+        ..PersistedState::default()
+    };
+
+    encode(&new)
+}
+
+/// Decode a single Candid value, tolerating trailing bytes
+/// (v1 payloads are read straight from stable memory, which is zero-padded to a page)
+fn decode<T: DeserializeOwned + CandidType>(bytes: &[u8]) -> Result<T, String> {
+    let mut de = candid::de::IDLDeserialize::new(bytes).map_err(|e| e.to_string())?;
+    de.get_value::<T>().map_err(|e| e.to_string())
+}
+
+fn encode<T: CandidType>(value: &T) -> Result<Vec<u8>, String> {
+    candid::encode_one(value).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KittyConfig;
+    use crate::pulse::DEFAULT_PULSE_INTERVAL_SECS;
+    use candid::Principal;
+
+    /// One of the built-in general templates
+    const BUILT_IN: &str =
+        "{kitty} observes your presence with {quantum} awareness. The path reveals itself one paw print at a time. {zen}";
+
+    fn texts(entries: &[WisdomEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.text.as_str()).collect()
+    }
+
+    fn v1_fixture() -> v1::PersistedState {
+        let section = |pairs: &[(&str, &[&str])]| {
+            pairs
+                .iter()
+                .map(|(key, texts)| (key.to_string(), texts.iter().map(|t| t.to_string()).collect()))
+                .collect()
+        };
+        v1::PersistedState {
+            kitty_state: v1::KittyState {
+                quantum_state: "Entangled".to_string(),
+                energy_level: 7,
+                zen_mood: "Playful".to_string(),
+                last_updated: 42,
+            },
+            templates: v1::WisdomTemplateStore {
+                templates: section(&[("general", &[BUILT_IN, "Nap now."]), ("custom", &["Stretch."])]),
+                quantum_adjectives: section(&[("Entangled", &["linked"])]),
+                zen_phrases: section(&[("Playful", &["chase the light"])]),
+            },
+            bonds: [(Principal::anonymous(), "Whiskers".to_string())].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn upgrades_a_v1_payload_to_the_current_schema() {
+        let state = migrate(1, &encode(&v1_fixture()).unwrap()).unwrap();
+
+        assert_eq!(state.kitty_state.quantum_state, "Entangled");
+        assert_eq!(state.kitty_state.energy_level, 7);
+        assert_eq!(state.kitty_state.last_updated, 42);
+        assert_eq!(state.bonds[&Principal::anonymous()], "Whiskers");
+
+        // IDs are assigned section by section, keys in sorted order
+        let templates = &state.templates.templates;
+        assert_eq!(texts(&templates["custom"]), ["Stretch."]);
+        assert_eq!(templates["custom"][0].id, 0);
+        assert_eq!(texts(&templates["general"]), [BUILT_IN, "Nap now."]);
+        assert_eq!(templates["general"].iter().map(|e| e.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(state.templates.quantum_adjectives["Entangled"][0].id, 3);
+        assert_eq!(state.templates.zen_phrases["Playful"][0].id, 4);
+        assert_eq!(state.templates.next_id, 5);
+        assert!(state.templates.dictionaries.is_empty());
+
+        for entry in templates.values().flatten() {
+            assert_eq!(entry.weight, DEFAULT_WEIGHT);
+            assert_eq!(entry.author, None);
+            assert_eq!(entry.deleted_at, None);
+        }
+
+        assert_eq!(state.pulse.interval_secs, DEFAULT_PULSE_INTERVAL_SECS);
+        assert!(state.pulse.history.is_empty());
+        assert_eq!(state.config.quantum_states, KittyConfig::default().quantum_states);
+        assert_eq!(state.config.default_context, "general");
+        assert!(state.roles.is_empty());
+        assert!(state.keywords.keywords.is_empty());
+        assert!(state.served.is_empty());
+    }
+
+    #[test]
+    fn tags_built_in_templates_by_their_exact_text() {
+        assert!(default_templates()["general"].iter().any(|text| text == BUILT_IN));
+        let state = migrate(1, &encode(&v1_fixture()).unwrap()).unwrap();
+
+        let general_tags = DEFAULT_TEMPLATE_TAGS.iter().find(|(context, _)| *context == "general").unwrap().1;
+        let general = &state.templates.templates["general"];
+        assert_eq!(general[0].tags, general_tags);
+        // An author-less template that isn't built in is a curator's, and stays untagged
+        assert!(general[1].tags.is_empty());
+        assert!(state.templates.templates["custom"][0].tags.is_empty());
+    }
+
+    #[test]
+    fn gives_every_bonded_user_a_personal_kitty() {
+        let state = migrate(1, &encode(&v1_fixture()).unwrap()).unwrap();
+
+        let kitty = &state.personal[&Principal::anonymous()];
+        assert_eq!(state.personal.len(), 1);
+        assert_eq!((kitty.energy_offset, kitty.mood_offset, kitty.affinity, kitty.visits), (0, 0, 0, 0));
+        assert_eq!(kitty.last_visit, None);
+    }
+
+    #[test]
+    fn tolerates_zero_padding_after_a_v1_payload() {
+        let mut bytes = encode(&v1_fixture()).unwrap();
+        bytes.resize(bytes.len() + 4096, 0);
+
+        let state = migrate(1, &bytes).unwrap();
+        assert_eq!(state.bonds.len(), 1);
+    }
+
+    #[test]
+    fn current_payload_round_trips_unchanged() {
+        let mut state = migrate(1, &encode(&v1_fixture()).unwrap()).unwrap();
+        state.personal.get_mut(&Principal::anonymous()).unwrap().visits = 9;
+        state.served.insert(Principal::anonymous(), vec![2, 1]);
+
+        let restored = migrate(SCHEMA_VERSION, &encode(&state).unwrap()).unwrap();
+        assert_eq!(texts(&restored.templates.templates["general"]), texts(&state.templates.templates["general"]));
+        assert_eq!(restored.templates.next_id, state.templates.next_id);
+        assert_eq!(restored.config.zen_moods, state.config.zen_moods);
+        assert_eq!(restored.personal[&Principal::anonymous()].visits, 9);
+        assert_eq!(restored.served[&Principal::anonymous()], [2, 1]);
+    }
+
+    #[test]
+    fn rejects_unknown_schema_versions() {
+        let bytes = encode(&v1_fixture()).unwrap();
+        assert!(migrate(0, &bytes).is_err());
+        assert!(migrate(SCHEMA_VERSION + 1, &bytes).is_err());
+    }
+
+    #[test]
+    fn reports_the_failing_step() {
+        let Err(error) = migrate(1, &encode(&"not a kitty").unwrap()) else {
+            panic!("a string decoded as a v1 payload");
+        };
+        assert!(error.starts_with("Migration v1 -> v2 failed"), "{}", error);
+    }
+}
@@ -0,0 +1,108 @@
+// Upgrade-safe persistence for the canister state
+//
+// Heap state (thread_local! RefCells) is wiped on every upgrade, so we serialize
+// everything worth keeping to stable memory in `pre_upgrade` and read it back in
+// `post_upgrade`.
+//
+// Stable memory holds a `(schema_version, payload)` pair where the payload is the
+// Candid-encoded `PersistedState` of that version. Older payloads are brought up
+// to date by the migration chain in `migrations.rs`.
+use candid::{CandidType, Principal};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::access::{self, Role};
+use crate::config::{self, KittyConfig};
+use crate::keywords::{self, KeywordRegistry};
+use crate::migrations;
+use crate::personal::{self, PersonalKitty};
+use crate::pulse::{self, PulseState};
+use crate::served;
+use crate::{KittyState, WisdomTemplateStore, GLOBAL_STATE, USER_KITTY_BONDS, WISDOM_TEMPLATES};
+
+/// Version of the `PersistedState` layout written by this wasm
+/// Bump this (and add a migration step) whenever a persisted type changes shape
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Everything that must survive a canister upgrade
+#[derive(CandidType, Deserialize, Default)]
+pub struct PersistedState {
+    pub(crate) kitty_state: KittyState,                     // Current quantum state of the kitty
+    pub(crate) templates: WisdomTemplateStore,              // Templates, adjectives, phrases and slot dictionaries (including user-authored ones)
+    pub(crate) bonds: HashMap<Principal, String>,           // User-kitty bonds keyed by principal
+    pub(crate) pulse: PulseState,                           // Pulse interval and recent pulse history
+    pub(crate) config: KittyConfig,                         // State/mood catalogue and rotation periods
+    pub(crate) roles: HashMap<Principal, Role>,             // Explicitly granted roles
+    pub(crate) keywords: KeywordRegistry,                   // Community keywords and each user's recent ones
+    pub(crate) served: HashMap<Principal, Vec<u64>>,        // Templates recently served to each user
+    pub(crate) personal: HashMap<Principal, PersonalKitty>, // Each bonded user's personal kitty
+}
+
+/// Collect the current heap state into a persistable snapshot
+fn snapshot() -> PersistedState {
+    PersistedState {
+        kitty_state: GLOBAL_STATE.with(|s| s.borrow().clone()),
+        templates: WISDOM_TEMPLATES.with(|s| s.borrow().clone()),
+        bonds: USER_KITTY_BONDS.with(|b| b.borrow().clone()),
+        pulse: pulse::snapshot(),
+        config: config::current(),
+        roles: access::snapshot(),
+        keywords: keywords::snapshot(),
+        served: served::snapshot(),
+        personal: personal::snapshot(),
+    }
+}
+
+/// Replace the heap state with a previously persisted snapshot
+/// Fails if the snapshot's configuration doesn't validate
+fn restore(state: PersistedState) -> Result<(), String> {
+    config::restore(state.config).map_err(|e| format!("invalid kitty configuration: {:?}", e))?;
+    GLOBAL_STATE.with(|s| *s.borrow_mut() = state.kitty_state);
+    WISDOM_TEMPLATES.with(|s| *s.borrow_mut() = state.templates);
+    USER_KITTY_BONDS.with(|b| *b.borrow_mut() = state.bonds);
+    pulse::restore(state.pulse);
+    access::restore(state.roles);
+    keywords::restore(state.keywords);
+    served::restore(state.served);
+    personal::restore(state.personal);
+    Ok(())
+}
+
+/// Write the current state to stable memory, tagged with the schema version
+/// Traps if serialization fails, which aborts the upgrade instead of losing data
+pub fn save_to_stable_memory() {
+    let payload = match candid::encode_one(snapshot()) {
+        Ok(payload) => payload,
+        Err(e) => ic_cdk::trap(&format!("Failed to encode state for stable memory: {}", e)),
+    };
+    
+    if let Err(e) = ic_cdk::storage::stable_save((SCHEMA_VERSION, payload)) {
+        ic_cdk::trap(&format!("Failed to save state to stable memory: {}", e));
+    }
+}
+
+/// Restore the state from stable memory, migrating it to the current schema
+///
+/// Returns false if stable memory is empty (nothing was persisted yet), in which
+/// case the caller should seed defaults. Traps if the persisted data can't be decoded
+/// or migrated, or its configuration is invalid, since silently resetting would throw
+/// away user-authored content.
+pub fn restore_from_stable_memory() -> bool {
+    if ic_cdk::api::stable::stable_size() == 0 {
+        return false;
+    }
+
+    let (version, payload) = match ic_cdk::storage::stable_restore::<(u32, Vec<u8>)>() {
+        Ok(versioned) => versioned,
+        // Schema v1 wrote the state directly, without a version header
+        Err(_) => (1, ic_cdk::api::stable::stable_bytes()),
+    };
+
+    match migrations::migrate(version, &payload).and_then(restore) {
+        Ok(()) => true,
+        Err(e) => ic_cdk::trap(&format!(
+            "Failed to restore state from stable memory (schema v{}): {}",
+            version, e
+        )),
+    }
+}
@@ -0,0 +1,164 @@
+// Personal kitty state for bonded users
+//
+// Every user shares the global quantum pulse, but a bonded user's kitty drifts from it
+// with their visits (wisdom requests):
+//   - energy: visits in quick succession tire the kitty (-1 each), coming back after
+//     six hours or more finds it rested (+2), and anything in between settles it one
+//     step back towards the global level; the offset stays within ±3
+//   - mood: every fifth visit shifts the kitty one mood further along the catalogue
+//   - affinity grows by one per visit, up to 100
+// The quantum state itself always follows the global pulse.
+use candid::{CandidType, Principal};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::config::KittyConfig;
+use crate::KittyState;
+
+/// Largest distance of a personal energy level from the global one
+const MAX_ENERGY_OFFSET: i8 = 3;
+
+/// Visits closer together than this tire the kitty (5 minutes)
+const TIRING_GAP_NS: u64 = 5 * 60 * 1_000_000_000;
+
+/// Visits at least this far apart find the kitty rested (6 hours)
+const RESTED_GAP_NS: u64 = 6 * 3600 * 1_000_000_000;
+
+/// Visits per shift of the personal mood
+const VISITS_PER_MOOD_SHIFT: u64 = 5;
+
+/// Highest affinity a kitty can have for its user
+const MAX_AFFINITY: u32 = 100;
+
+thread_local! {
+    static PERSONAL_KITTIES: RefCell<HashMap<Principal, PersonalKitty>> = RefCell::new(HashMap::new());
+}
+
+/// PersonalKitty is how a bonded user's kitty differs from the global one
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct PersonalKitty {
+    pub(crate) energy_offset: i8,       // Added to the global energy level, within the configured bounds
+    pub(crate) mood_offset: u8,         // Steps along the zen mood catalogue from the global mood, kept below its length
+    pub(crate) affinity: u32,           // How attached the kitty is to its user, 0-100
+    pub(crate) visits: u64,             // Wisdom requests made since bonding
+    pub(crate) last_visit: Option<u64>, // IC time of the latest visit
+}
+
+/// PersonalKittyView is a bonded user's kitty record with the state it shows them
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PersonalKittyView {
+    kitty: PersonalKitty,
+    state: KittyState, // The global state with the personal energy and mood applied
+}
+
+impl PersonalKitty {
+    /// The global state as this kitty shows it
+    pub fn view(&self, global: &KittyState, config: &KittyConfig) -> KittyState {
+        let energy = i16::from(global.energy_level) + i16::from(self.energy_offset);
+        let energy_level = energy.clamp(i16::from(config.energy_min), i16::from(config.energy_max)) as u8;
+
+        let zen_mood = match config.zen_moods.iter().position(|m| *m == global.zen_mood) {
+            Some(index) => config.zen_moods[(index + usize::from(self.mood_offset)) % config.zen_moods.len()].clone(),
+            // The mood isn't in the catalogue (it changed since the last pulse); show it as is
+            None => global.zen_mood.clone(),
+        };
+
+        KittyState {
+            quantum_state: global.quantum_state.clone(),
+            energy_level,
+            zen_mood,
+            last_updated: global.last_updated,
+        }
+    }
+
+    /// Let a visit at `now` change the kitty, with `mood_count` moods in the catalogue
+    fn visit(&mut self, now: u64, mood_count: usize) {
+        if let Some(last) = self.last_visit {
+            let gap = now.saturating_sub(last);
+            self.energy_offset = if gap < TIRING_GAP_NS {
+                self.energy_offset - 1
+            } else if gap >= RESTED_GAP_NS {
+                self.energy_offset + 2
+            } else {
+                self.energy_offset - self.energy_offset.signum()
+            }
+            .clamp(-MAX_ENERGY_OFFSET, MAX_ENERGY_OFFSET);
+        }
+
+        self.visits += 1;
+        if self.visits.is_multiple_of(VISITS_PER_MOOD_SHIFT) {
+            // Wrap at the catalogue length so the offset keeps cycling through every mood;
+            // a catalogue longer than a u8 can count cycles through its first 256
+            let next = (usize::from(self.mood_offset) + 1) % mood_count.max(1);
+            self.mood_offset = u8::try_from(next).unwrap_or(0);
+        }
+        self.affinity = (self.affinity + 1).min(MAX_AFFINITY);
+        self.last_visit = Some(now);
+    }
+}
+
+/// The state `principal` sees: their personal view if they have a kitty, else the global state
+pub fn state_for(principal: Option<Principal>, global: &KittyState, config: &KittyConfig) -> KittyState {
+    principal
+        .and_then(|p| PERSONAL_KITTIES.with(|k| k.borrow().get(&p).map(|kitty| kitty.view(global, config))))
+        .unwrap_or_else(|| global.clone())
+}
+
+/// Give `principal` a personal kitty if they don't have one yet
+pub fn bond(principal: Principal) {
+    PERSONAL_KITTIES.with(|k| {
+        k.borrow_mut().entry(principal).or_default();
+    });
+}
+
+/// Record a visit by `principal` at `now`, if they have a personal kitty
+pub fn record_visit(principal: Principal, now: u64, config: &KittyConfig) {
+    PERSONAL_KITTIES.with(|k| {
+        if let Some(kitty) = k.borrow_mut().get_mut(&principal) {
+            kitty.visit(now, config.zen_moods.len());
+        }
+    });
+}
+
+/// `principal`'s kitty and the state it shows them, if they have one
+pub fn view(principal: &Principal, global: &KittyState, config: &KittyConfig) -> Option<PersonalKittyView> {
+    PERSONAL_KITTIES.with(|k| {
+        k.borrow().get(principal).map(|kitty| PersonalKittyView {
+            kitty: kitty.clone(),
+            state: kitty.view(global, config),
+        })
+    })
+}
+
+/// Get a copy of every personal kitty (used for persistence)
+pub fn snapshot() -> HashMap<Principal, PersonalKitty> {
+    PERSONAL_KITTIES.with(|k| k.borrow().clone())
+}
+
+/// Replace every personal kitty (used when restoring after an upgrade)
+pub fn restore(kitties: HashMap<Principal, PersonalKitty>) {
+    PERSONAL_KITTIES.with(|k| *k.borrow_mut() = kitties);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mood_keeps_shifting_after_many_visits() {
+        let config = KittyConfig::default();
+        let mood_count = config.zen_moods.len();
+        let mut kitty = PersonalKitty::default();
+        let mut offsets = Vec::new();
+        for visit in 1..=VISITS_PER_MOOD_SHIFT * 300 {
+            kitty.visit(visit * RESTED_GAP_NS, mood_count);
+            if visit % VISITS_PER_MOOD_SHIFT == 0 {
+                offsets.push(usize::from(kitty.mood_offset));
+            }
+        }
+
+        let expected: Vec<usize> = (1..=300).map(|shift| shift % mood_count).collect();
+        assert_eq!(offsets, expected);
+    }
+}
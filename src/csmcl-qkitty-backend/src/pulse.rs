@@ -0,0 +1,143 @@
+// Timer-driven quantum pulse
+//
+// Query calls can't persist state changes, so the kitty's state is advanced by a
+// recurring timer instead of being refreshed lazily inside queries. Timers don't
+// survive upgrades, so `start_pulse_timer` must be called from both `init` and
+// `post_upgrade`.
+use candid::CandidType;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::time::Duration;
+
+use ic_cdk_timers::TimerId;
+
+use crate::KittyState;
+
+/// Default time between pulses (30 minutes, matching the energy cycle)
+pub const DEFAULT_PULSE_INTERVAL_SECS: u64 = 1800;
+
+/// Shortest allowed pulse interval, to keep a misconfigured kitty from burning cycles
+pub const MIN_PULSE_INTERVAL_SECS: u64 = 10;
+
+/// Number of pulses kept in the inspectable history
+const PULSE_HISTORY_LIMIT: usize = 48;
+
+thread_local! {
+    static PULSE_STATE: RefCell<PulseState> = RefCell::new(PulseState::default());
+    static PULSE_TIMER: RefCell<Option<TimerId>> = const { RefCell::new(None) };
+}
+
+/// PulseRecord captures the kitty's state right after a pulse
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PulseRecord {
+    pub(crate) timestamp: u64,        // IC time of the pulse
+    pub(crate) quantum_state: String, // Quantum state after the pulse
+    pub(crate) energy_level: u8,      // Energy level after the pulse
+    pub(crate) zen_mood: String,      // Zen mood after the pulse
+}
+
+/// PulseState holds the pulse configuration and recent history
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PulseState {
+    pub(crate) interval_secs: u64,            // Seconds between timer pulses
+    pub(crate) history: Vec<PulseRecord>,     // Most recent pulses, oldest first
+}
+
+impl Default for PulseState {
+    fn default() -> Self {
+        Self {
+            interval_secs: DEFAULT_PULSE_INTERVAL_SECS,
+            history: Vec::new(),
+        }
+    }
+}
+
+/// Get a copy of the pulse state (used for persistence)
+pub fn snapshot() -> PulseState {
+    PULSE_STATE.with(|p| p.borrow().clone())
+}
+
+/// Replace the pulse state (used when restoring after an upgrade)
+pub fn restore(state: PulseState) {
+    PULSE_STATE.with(|p| *p.borrow_mut() = state);
+}
+
+/// Append a pulse to the history, dropping the oldest entries beyond the limit
+pub fn record_pulse(state: &KittyState) {
+    PULSE_STATE.with(|p| {
+        let mut pulse = p.borrow_mut();
+        pulse.history.push(PulseRecord {
+            timestamp: state.last_updated,
+            quantum_state: state.quantum_state.clone(),
+            energy_level: state.energy_level,
+            zen_mood: state.zen_mood.clone(),
+        });
+
+        let overflow = pulse.history.len().saturating_sub(PULSE_HISTORY_LIMIT);
+        pulse.history.drain(..overflow);
+    });
+}
+
+/// Recent pulses, oldest first
+pub fn history() -> Vec<PulseRecord> {
+    PULSE_STATE.with(|p| p.borrow().history.clone())
+}
+
+/// Current pulse interval in seconds
+pub fn interval_secs() -> u64 {
+    PULSE_STATE.with(|p| p.borrow().interval_secs)
+}
+
+/// Change the pulse interval and restart the timer with it
+pub fn set_interval_secs(secs: u64) {
+    PULSE_STATE.with(|p| p.borrow_mut().interval_secs = secs);
+    start_pulse_timer();
+}
+
+/// (Re)register the recurring pulse timer using the configured interval
+pub fn start_pulse_timer() {
+    let interval = Duration::from_secs(interval_secs());
+    let timer_id = ic_cdk_timers::set_timer_interval(interval, || {
+        crate::pulse_kitty_state();
+    });
+
+    PULSE_TIMER.with(|t| {
+        if let Some(old_id) = t.borrow_mut().replace(timer_id) {
+            ic_cdk_timers::clear_timer(old_id);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(last_updated: u64) -> KittyState {
+        KittyState {
+            quantum_state: "Coherent".to_string(),
+            energy_level: 4,
+            zen_mood: "Tranquil".to_string(),
+            last_updated,
+        }
+    }
+
+    #[test]
+    fn a_pulse_records_the_state_it_produced() {
+        record_pulse(&state(7));
+        let history = history();
+        assert_eq!(history.len(), 1);
+        let pulse = &history[0];
+        assert_eq!((pulse.timestamp, pulse.energy_level), (7, 4));
+        assert_eq!((pulse.quantum_state.as_str(), pulse.zen_mood.as_str()), ("Coherent", "Tranquil"));
+    }
+
+    #[test]
+    fn history_keeps_the_latest_48_pulses_oldest_first() {
+        for timestamp in 0..60 {
+            record_pulse(&state(timestamp));
+        }
+        let timestamps: Vec<u64> = history().iter().map(|p| p.timestamp).collect();
+        assert_eq!(timestamps, (12..60).collect::<Vec<_>>());
+        assert_eq!(interval_secs(), DEFAULT_PULSE_INTERVAL_SECS);
+    }
+}
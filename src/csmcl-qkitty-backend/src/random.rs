@@ -0,0 +1,172 @@
+// Randomness for wisdom and greeting selection
+//
+// Choices used to be indexed by the current second, so everyone calling within the
+// same second got the same result and the template, adjective and phrase moved in
+// lockstep. Instead:
+//   - the canister keeps a 32-byte seed from the management canister's `raw_rand`,
+//     fetched right after init/upgrade and refreshed by a timer every hour
+//   - each call derives its own 64-bit seed by hashing the canister seed with a call
+//     counter, the caller, the request data and the time
+//   - an `Rng` seeded with it makes every choice within the call
+//
+// Queries can't advance the counter, so identical queries from one caller in the same
+// instant get the same seed; a different caller or request diverges. The seed isn't
+// persisted: an upgrade fetches a fresh one, and until it arrives calls are seeded
+// from the counter, caller, request and time alone.
+use candid::Principal;
+use sha2::{Digest, Sha256};
+use ic_cdk_timers::TimerId;
+use std::cell::RefCell;
+use std::time::Duration;
+
+/// Time between reseeds from `raw_rand`
+const RESEED_INTERVAL: Duration = Duration::from_secs(3600);
+
+thread_local! {
+    static CANISTER_SEED: RefCell<[u8; 32]> = const { RefCell::new([0; 32]) };
+    static CALL_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    static RESEED_TIMERS: RefCell<Vec<TimerId>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Rng is a SplitMix64 generator: small, fast and fully determined by its seed
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform index below `n` (which must be non-zero)
+    pub fn below(&mut self, n: usize) -> usize {
+        // Multiply-shift keeps the bias negligible for the small `n` used here
+        ((u128::from(self.next_u64()) * n as u128) >> 64) as usize
+    }
+
+    /// An index into `weights` drawn in proportion to its weight (None if they're all zero)
+    pub fn weighted(&mut self, weights: &[u64]) -> Option<usize> {
+        let total: u64 = weights.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = self.next_u64() % total;
+        weights.iter().position(|&weight| {
+            let hit = roll < weight;
+            roll = roll.saturating_sub(weight);
+            hit
+        })
+    }
+}
+
+/// A fresh seed for one call by `caller` with `request` data at IC time `now`
+pub fn call_seed(caller: Principal, request: &[u8], now: u64) -> u64 {
+    let counter = CALL_COUNTER.with(|c| {
+        let mut c = c.borrow_mut();
+        *c = c.wrapping_add(1);
+        *c
+    });
+
+    let mut hasher = Sha256::new();
+    CANISTER_SEED.with(|s| hasher.update(*s.borrow()));
+    hasher.update(counter.to_le_bytes());
+    hasher.update(caller.as_slice());
+    hasher.update(now.to_le_bytes());
+    hasher.update(request);
+    let digest = hasher.finalize();
+
+    let mut seed = [0; 8];
+    seed.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(seed)
+}
+
+/// Fetch a seed now and keep refreshing it
+/// Timers don't survive upgrades, so call this from both `init` and `post_upgrade`.
+/// Calling it again replaces the running timers rather than adding more.
+pub fn start_reseed_timer() {
+    let timer_ids = vec![
+        ic_cdk_timers::set_timer(Duration::ZERO, || ic_cdk::spawn(reseed())),
+        ic_cdk_timers::set_timer_interval(RESEED_INTERVAL, || ic_cdk::spawn(reseed())),
+    ];
+
+    RESEED_TIMERS.with(|t| {
+        for old_id in t.replace(timer_ids) {
+            ic_cdk_timers::clear_timer(old_id);
+        }
+    });
+}
+
+/// Replace the canister seed with fresh bytes from `raw_rand`
+async fn reseed() {
+    // On failure the current seed stays in use until the next interval tries again
+    if let Ok((bytes,)) = ic_cdk::api::management_canister::main::raw_rand().await {
+        let mut hasher = Sha256::new();
+        hasher.update(&bytes);
+        CANISTER_SEED.with(|s| *s.borrow_mut() = hasher.finalize().into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_gives_the_same_sequence() {
+        let mut a = Rng::from_seed(42);
+        let mut b = Rng::from_seed(42);
+        let mut c = Rng::from_seed(43);
+        let first: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        assert_eq!(first, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(first, (0..8).map(|_| c.next_u64()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn below_stays_below_its_bound() {
+        let mut rng = Rng::from_seed(1);
+        for n in 1..50 {
+            for _ in 0..50 {
+                assert!(rng.below(n) < n);
+            }
+        }
+        let mut seen = [false; 5];
+        for _ in 0..200 {
+            seen[rng.below(5)] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+    }
+
+    #[test]
+    fn weighted_never_picks_a_zero_weight() {
+        let mut rng = Rng::from_seed(2);
+        for _ in 0..1000 {
+            let pick = rng.weighted(&[0, 3, 0, 1, 0]).unwrap();
+            assert!(pick == 1 || pick == 3);
+        }
+        assert_eq!(rng.weighted(&[0, 0]), None);
+        assert_eq!(rng.weighted(&[]), None);
+    }
+
+    #[test]
+    fn call_seeds_differ_between_calls_and_callers() {
+        let alice = Principal::from_slice(&[1]);
+        let bob = Principal::from_slice(&[2]);
+        let first = call_seed(alice, b"general", 5);
+        assert_ne!(first, call_seed(alice, b"general", 5));
+        assert_ne!(call_seed(alice, b"general", 5), call_seed(bob, b"general", 5));
+
+        // Without the counter advancing, the seed depends only on its inputs
+        CALL_COUNTER.with(|c| *c.borrow_mut() = 0);
+        assert_eq!(call_seed(alice, b"general", 5), first);
+        CALL_COUNTER.with(|c| *c.borrow_mut() = 0);
+        assert_ne!(call_seed(alice, b"team", 5), first);
+    }
+}
@@ -0,0 +1,67 @@
+// Per-user memory of recently served templates
+//
+// Each authenticated principal keeps a short window of the templates it was served,
+// most recent first. Selection skips templates in the window and, when every
+// candidate is in it (a small pool), takes the one served longest ago, so a returning
+// user rarely sees a repeat. Anonymous callers all share one principal, so they have
+// no window.
+use candid::Principal;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Templates remembered per principal
+const RECENT_WINDOW: usize = 8;
+
+thread_local! {
+    static SERVED_TEMPLATES: RefCell<HashMap<Principal, Vec<u64>>> = RefCell::new(HashMap::new());
+}
+
+/// Templates recently served to `principal`, most recent first
+pub fn recent(principal: &Principal) -> Vec<u64> {
+    SERVED_TEMPLATES.with(|s| s.borrow().get(principal).cloned().unwrap_or_default())
+}
+
+/// Remember that `template_id` was served to `principal`
+pub fn record(principal: Principal, template_id: u64) {
+    SERVED_TEMPLATES.with(|s| {
+        let mut served = s.borrow_mut();
+        let recent = served.entry(principal).or_default();
+        recent.retain(|&id| id != template_id);
+        recent.insert(0, template_id);
+        recent.truncate(RECENT_WINDOW);
+    });
+}
+
+/// Get a copy of every window (used for persistence)
+pub fn snapshot() -> HashMap<Principal, Vec<u64>> {
+    SERVED_TEMPLATES.with(|s| s.borrow().clone())
+}
+
+/// Replace every window (used when restoring after an upgrade)
+pub fn restore(served: HashMap<Principal, Vec<u64>>) {
+    SERVED_TEMPLATES.with(|s| *s.borrow_mut() = served);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_eight_templates_most_recent_first() {
+        let user = Principal::from_slice(&[1]);
+        for id in 0..10 {
+            record(user, id);
+        }
+        assert_eq!(recent(&user), [9, 8, 7, 6, 5, 4, 3, 2]);
+        assert!(recent(&Principal::from_slice(&[2])).is_empty());
+    }
+
+    #[test]
+    fn serving_a_template_again_moves_it_to_the_front() {
+        let user = Principal::from_slice(&[1]);
+        for id in [1, 2, 3, 1] {
+            record(user, id);
+        }
+        assert_eq!(recent(&user), [1, 3, 2]);
+    }
+}
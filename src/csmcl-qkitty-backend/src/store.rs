@@ -0,0 +1,922 @@
+// Wisdom template store: templates, quantum adjectives, zen phrases and slot dictionaries
+//
+// Slot dictionaries are curator-defined word lists that templates reference by name,
+// e.g. `{animal}`. Like adjectives (keyed by quantum state) and phrases (keyed by zen
+// mood), a dictionary's words can be keyed by state, mood or context, or not keyed at all.
+//
+// Every entry carries a stable ID (unique across all vocabularies) so curators
+// can edit or remove a single entry, plus tags, attribution, timestamps and usage
+// counters. Deletion is soft: deleted entries are hidden from wisdom generation and
+// listings but can be restored.
+use candid::{CandidType, Principal};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::error::KittyError;
+
+/// The vocabularies held by the store
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Vocabulary {
+    Template,  // Wisdom templates, keyed by context
+    Adjective, // Quantum adjectives, keyed by quantum state
+    Phrase,    // Zen phrases, keyed by zen mood
+    Word,      // Slot dictionary words, keyed by dictionary and then by its SlotKey
+}
+
+impl Vocabulary {
+    const ALL: [Vocabulary; 4] = [Vocabulary::Template, Vocabulary::Adjective, Vocabulary::Phrase, Vocabulary::Word];
+
+    fn label(self) -> &'static str {
+        match self {
+            Vocabulary::Template => "template",
+            Vocabulary::Adjective => "adjective",
+            Vocabulary::Phrase => "phrase",
+            Vocabulary::Word => "word",
+        }
+    }
+}
+
+/// What a slot dictionary's words are keyed by
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotKey {
+    Unkeyed, // One word list, whatever the kitty's state
+    State,   // Words per quantum state
+    Mood,    // Words per zen mood
+    Context, // Words per wisdom context
+}
+
+/// Key that holds a dictionary's words for any state, mood or context
+/// Keyed dictionaries fall back to these when the current key has no words.
+pub const ANY_KEY: &str = "";
+
+/// SlotDictionary is a curator-defined word list referenced from templates as `{name}`
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SlotDictionary {
+    pub(crate) keyed_by: SlotKey,                         // What the words are keyed by
+    pub(crate) words: HashMap<String, Vec<WisdomEntry>>,  // Words by key (ANY_KEY for any)
+}
+
+/// SlotDictionaryInfo summarizes a slot dictionary
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SlotDictionaryInfo {
+    name: String,      // Placeholder name used in templates
+    keyed_by: SlotKey, // What the words are keyed by
+    word_count: u32,   // Number of active words across all keys
+}
+
+/// Entries of one vocabulary (or one slot dictionary), by key
+type Section = HashMap<String, Vec<WisdomEntry>>;
+
+/// Where an entry lives: its dictionary (words only) and key
+struct Location {
+    dictionary: Option<String>,
+    key: String,
+}
+
+/// Most tags an entry may carry
+pub const MAX_TAGS: usize = 10;
+
+/// Selection weight of a template no curator has weighted
+pub const DEFAULT_WEIGHT: u32 = 1;
+
+/// Highest selection weight a template may be given
+pub const MAX_WEIGHT: u32 = 100;
+
+/// WisdomEntry is a single template, adjective or phrase
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct WisdomEntry {
+    pub(crate) id: u64,                    // Stable ID, unique across the store
+    pub(crate) text: String,               // The entry's content
+    pub(crate) tags: Vec<String>,          // Curator-assigned tags
+    pub(crate) weight: u32,                // Relative chance of being picked (templates only; 0 disables it)
+    pub(crate) author: Option<Principal>,  // Who added it (None for built-in defaults)
+    pub(crate) created_at: u64,            // When it was added (0 if added before this was tracked)
+    pub(crate) updated_at: u64,            // When its text, tags or weight last changed
+    pub(crate) usage_count: u64,           // How many wisdoms it has been used in
+    pub(crate) last_used_at: Option<u64>,  // When it was last used
+    pub(crate) deleted_at: Option<u64>,    // When the entry was soft-deleted, if it was
+}
+
+impl WisdomEntry {
+    fn new(id: u64, text: String, author: Option<Principal>, now: u64) -> Self {
+        Self {
+            id,
+            text,
+            tags: Vec::new(),
+            weight: DEFAULT_WEIGHT,
+            author,
+            created_at: now,
+            updated_at: now,
+            usage_count: 0,
+            last_used_at: None,
+            deleted_at: None,
+        }
+    }
+}
+
+/// WisdomTemplate is a wisdom template together with the context it belongs to
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct WisdomTemplate {
+    id: u64,                   // Stable ID, unique across the store
+    context: String,           // The context the template is filed under
+    text: String,              // The template text with placeholders
+    tags: Vec<String>,         // Curator-assigned tags
+    weight: u32,               // Relative chance of being picked (0 disables it)
+    author: Option<Principal>, // Who added it (None for built-in defaults)
+    created_at: u64,           // When it was added (0 if added before this was tracked)
+    updated_at: u64,           // When its text, tags or weight last changed
+    usage_count: u64,          // How many wisdoms it has been used in
+    last_used_at: Option<u64>, // When it was last used
+}
+
+impl WisdomTemplate {
+    fn from_entry(context: &str, entry: &WisdomEntry) -> Self {
+        Self {
+            id: entry.id,
+            context: context.to_string(),
+            text: entry.text.clone(),
+            tags: entry.tags.clone(),
+            weight: entry.weight,
+            author: entry.author,
+            created_at: entry.created_at,
+            updated_at: entry.updated_at,
+            usage_count: entry.usage_count,
+            last_used_at: entry.last_used_at,
+        }
+    }
+}
+
+/// DeletedEntry describes a soft-deleted entry awaiting restore
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct DeletedEntry {
+    vocabulary: Vocabulary,     // Which vocabulary the entry belongs to
+    dictionary: Option<String>, // The slot dictionary, for words
+    key: String,                // Its context, quantum state or zen mood
+    entry: WisdomEntry,         // The entry itself
+}
+
+/// Default number of entries per page when listing
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+
+/// Largest page size a caller may request, keeping responses well under the message size limit
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// KeyCount pairs a context, quantum state or zen mood with its number of active entries
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct KeyCount {
+    name: String, // The context, quantum state or zen mood
+    count: u32,   // Number of active entries under it
+}
+
+impl KeyCount {
+    pub fn new(name: String, count: u32) -> Self {
+        Self { name, count }
+    }
+}
+
+/// PageRequest selects a page of templates
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct PageRequest {
+    pub(crate) context: Option<String>, // Only list this context (all contexts if None)
+    pub(crate) filter: Option<String>,  // Case-insensitive substring the text must contain
+    pub(crate) cursor: Option<u64>,     // Continue after this entry ID (from a previous page's next_cursor)
+    pub(crate) page_size: Option<u32>,  // Entries per page (default 20, max 100)
+}
+
+/// TemplatePage is one page of a template listing
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TemplatePage {
+    templates: Vec<WisdomTemplate>, // Templates in ascending ID order
+    next_cursor: Option<u64>,       // Pass as `cursor` to get the next page; None on the last page
+}
+
+/// WisdomTemplateStore holds all the templates and phrases for wisdom generation
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct WisdomTemplateStore {
+    // Templates organized by context
+    pub(crate) templates: HashMap<String, Vec<WisdomEntry>>,
+
+    // Quantum adjectives by state
+    pub(crate) quantum_adjectives: HashMap<String, Vec<WisdomEntry>>,
+
+    // Zen phrases by mood
+    pub(crate) zen_phrases: HashMap<String, Vec<WisdomEntry>>,
+
+    // Slot dictionaries by placeholder name
+    pub(crate) dictionaries: HashMap<String, SlotDictionary>,
+
+    // Next ID to hand out
+    pub(crate) next_id: u64,
+}
+
+impl WisdomTemplateStore {
+    /// Build a store from plain text vocabularies, assigning fresh IDs
+    pub fn from_text(
+        templates: HashMap<String, Vec<String>>,
+        quantum_adjectives: HashMap<String, Vec<String>>,
+        zen_phrases: HashMap<String, Vec<String>>,
+        now: u64,
+    ) -> Self {
+        let mut store = Self::default();
+        for (vocabulary, section) in [
+            (Vocabulary::Template, templates),
+            (Vocabulary::Adjective, quantum_adjectives),
+            (Vocabulary::Phrase, zen_phrases),
+        ] {
+            // Sort keys so IDs are assigned deterministically
+            let mut section: Vec<_> = section.into_iter().collect();
+            section.sort_by(|a, b| a.0.cmp(&b.0));
+            for (key, texts) in section {
+                let entries = texts
+                    .into_iter()
+                    .map(|text| WisdomEntry::new(store.allocate_id(), text, None, now))
+                    .collect();
+                store.section_mut(vocabulary).expect("fixed vocabulary").insert(key, entries);
+            }
+        }
+        store
+    }
+
+    fn allocate_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// The entries of a fixed vocabulary (None for words, which live in dictionaries)
+    fn section(&self, vocabulary: Vocabulary) -> Option<&Section> {
+        match vocabulary {
+            Vocabulary::Template => Some(&self.templates),
+            Vocabulary::Adjective => Some(&self.quantum_adjectives),
+            Vocabulary::Phrase => Some(&self.zen_phrases),
+            Vocabulary::Word => None,
+        }
+    }
+
+    fn section_mut(&mut self, vocabulary: Vocabulary) -> Option<&mut Section> {
+        match vocabulary {
+            Vocabulary::Template => Some(&mut self.templates),
+            Vocabulary::Adjective => Some(&mut self.quantum_adjectives),
+            Vocabulary::Phrase => Some(&mut self.zen_phrases),
+            Vocabulary::Word => None,
+        }
+    }
+
+    /// Every entry list in a vocabulary, with its location
+    fn sections(&self, vocabulary: Vocabulary) -> Vec<(Option<&str>, &Section)> {
+        match self.section(vocabulary) {
+            Some(section) => vec![(None, section)],
+            None => self.dictionaries.iter().map(|(name, d)| (Some(name.as_str()), &d.words)).collect(),
+        }
+    }
+
+    /// Find an entry by ID, returning its location and a mutable reference
+    fn find_mut(&mut self, vocabulary: Vocabulary, id: u64) -> Result<(Location, &mut WisdomEntry), KittyError> {
+        let sections: Vec<(Option<String>, &mut Section)> = match vocabulary {
+            Vocabulary::Word => self.dictionaries.iter_mut().map(|(name, d)| (Some(name.clone()), &mut d.words)).collect(),
+            _ => vec![(None, self.section_mut(vocabulary).expect("fixed vocabulary"))],
+        };
+        sections
+            .into_iter()
+            .flat_map(|(dictionary, section)| section.iter_mut().map(move |(key, entries)| (dictionary.clone(), key, entries)))
+            .find_map(|(dictionary, key, entries)| {
+                entries
+                    .iter_mut()
+                    .find(|e| e.id == id)
+                    .map(|e| (Location { dictionary, key: key.clone() }, e))
+            })
+            .ok_or_else(|| KittyError::NotFound(format!("No {} with ID {}", vocabulary.label(), id)))
+    }
+
+    /// The entries at a location, if any exist
+    fn entries_at(&self, vocabulary: Vocabulary, location: &Location) -> Option<&Vec<WisdomEntry>> {
+        match &location.dictionary {
+            Some(name) => self.dictionaries.get(name)?.words.get(&location.key),
+            None => self.section(vocabulary)?.get(&location.key),
+        }
+    }
+
+    /// Reject `text` if an active entry at `location` (other than `except_id`) already has it
+    fn ensure_unique(&self, vocabulary: Vocabulary, location: &Location, text: &str, except_id: Option<u64>) -> Result<(), KittyError> {
+        let clash = self.entries_at(vocabulary, location).and_then(|entries| {
+            entries
+                .iter()
+                .find(|e| e.deleted_at.is_none() && e.text == text && Some(e.id) != except_id)
+        });
+        match clash {
+            Some(existing) => Err(KittyError::Duplicate(format!(
+                "This {} already exists under '{}' (ID {})",
+                vocabulary.label(),
+                location.key,
+                existing.id
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Add an entry at `location`, returning its new ID
+    fn insert(&mut self, vocabulary: Vocabulary, location: Location, text: String, author: Principal, now: u64) -> Result<u64, KittyError> {
+        self.ensure_unique(vocabulary, &location, &text, None)?;
+        let id = self.allocate_id();
+        let section = match &location.dictionary {
+            Some(name) => &mut self
+                .dictionaries
+                .get_mut(name)
+                .ok_or_else(|| KittyError::NotFound(format!("No slot dictionary named '{}'", name)))?
+                .words,
+            None => self.section_mut(vocabulary).expect("fixed vocabulary"),
+        };
+        section
+            .entry(location.key)
+            .or_default()
+            .push(WisdomEntry::new(id, text, Some(author), now));
+        Ok(id)
+    }
+
+    /// Add an entry under `key` on behalf of `author`, returning its new ID
+    pub fn add(&mut self, vocabulary: Vocabulary, key: String, text: String, author: Principal, now: u64) -> Result<u64, KittyError> {
+        self.insert(vocabulary, Location { dictionary: None, key }, text, author, now)
+    }
+
+    /// Add a word to a slot dictionary under `key` (ANY_KEY for any), returning its new ID
+    pub fn add_word(&mut self, dictionary: &str, key: String, text: String, author: Principal, now: u64) -> Result<u64, KittyError> {
+        let location = Location { dictionary: Some(dictionary.to_string()), key };
+        self.insert(Vocabulary::Word, location, text, author, now)
+    }
+
+    /// Define a new, empty slot dictionary
+    pub fn define_dictionary(&mut self, name: String, keyed_by: SlotKey) -> Result<(), KittyError> {
+        if let Some(existing) = self.dictionaries.get(&name) {
+            return Err(KittyError::Duplicate(format!(
+                "A slot dictionary named '{}' already exists (keyed by {:?})",
+                name, existing.keyed_by
+            )));
+        }
+        self.dictionaries.insert(name, SlotDictionary { keyed_by, words: HashMap::new() });
+        Ok(())
+    }
+
+    /// Look up a slot dictionary by name
+    pub fn dictionary(&self, name: &str) -> Result<&SlotDictionary, KittyError> {
+        self.dictionaries
+            .get(name)
+            .ok_or_else(|| KittyError::NotFound(format!("No slot dictionary named '{}'", name)))
+    }
+
+    /// Every slot dictionary with its active word count, sorted by name
+    pub fn dictionary_infos(&self) -> Vec<SlotDictionaryInfo> {
+        let mut infos: Vec<SlotDictionaryInfo> = self
+            .dictionaries
+            .iter()
+            .map(|(name, d)| SlotDictionaryInfo {
+                name: name.clone(),
+                keyed_by: d.keyed_by,
+                word_count: d.words.values().flatten().filter(|e| e.deleted_at.is_none()).count() as u32,
+            })
+            .collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    /// Active words in a dictionary under `key`, falling back to its ANY_KEY words
+    pub fn active_words(&self, dictionary: &str, key: &str) -> Vec<WisdomEntry> {
+        let Some(dictionary) = self.dictionaries.get(dictionary) else {
+            return Vec::new();
+        };
+        let active = |key: &str| -> Vec<WisdomEntry> {
+            dictionary
+                .words
+                .get(key)
+                .map(|entries| entries.iter().filter(|e| e.deleted_at.is_none()).cloned().collect())
+                .unwrap_or_default()
+        };
+        let words = active(key);
+        if words.is_empty() && key != ANY_KEY {
+            active(ANY_KEY)
+        } else {
+            words
+        }
+    }
+
+    /// Active word entries in a dictionary under exactly `key`
+    pub fn word_entries(&self, dictionary: &str, key: &str) -> Result<Vec<WisdomEntry>, KittyError> {
+        Ok(self
+            .dictionary(dictionary)?
+            .words
+            .get(key)
+            .map(|entries| entries.iter().filter(|e| e.deleted_at.is_none()).cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// Replace the text of an existing entry
+    pub fn update(&mut self, vocabulary: Vocabulary, id: u64, text: String, now: u64) -> Result<(), KittyError> {
+        let (location, _) = self.find_mut(vocabulary, id)?;
+        self.ensure_unique(vocabulary, &location, &text, Some(id))?;
+        let (_, entry) = self.find_mut(vocabulary, id)?;
+        entry.text = text;
+        entry.updated_at = now;
+        Ok(())
+    }
+
+    /// Replace the tags of an existing entry
+    pub fn set_tags(&mut self, vocabulary: Vocabulary, id: u64, tags: Vec<String>, now: u64) -> Result<(), KittyError> {
+        if tags.len() > MAX_TAGS {
+            return Err(KittyError::InvalidInput(format!("At most {} tags are allowed", MAX_TAGS)));
+        }
+        let (_, entry) = self.find_mut(vocabulary, id)?;
+        entry.tags = tags;
+        entry.updated_at = now;
+        Ok(())
+    }
+
+    /// Set the selection weight of a template
+    pub fn set_weight(&mut self, id: u64, weight: u32, now: u64) -> Result<(), KittyError> {
+        if weight > MAX_WEIGHT {
+            return Err(KittyError::InvalidInput(format!("weight must be at most {}", MAX_WEIGHT)));
+        }
+        let (_, entry) = self.find_mut(Vocabulary::Template, id)?;
+        entry.weight = weight;
+        entry.updated_at = now;
+        Ok(())
+    }
+
+    /// Count a use of an entry in generated wisdom
+    pub fn record_usage(&mut self, vocabulary: Vocabulary, id: u64, now: u64) {
+        if let Ok((_, entry)) = self.find_mut(vocabulary, id) {
+            entry.usage_count += 1;
+            entry.last_used_at = Some(now);
+        }
+    }
+
+    /// Soft-delete an entry
+    pub fn delete(&mut self, vocabulary: Vocabulary, id: u64, now: u64) -> Result<(), KittyError> {
+        let (_, entry) = self.find_mut(vocabulary, id)?;
+        if entry.deleted_at.is_some() {
+            return Err(KittyError::NotFound(format!("{} {} is already deleted", vocabulary.label(), id)));
+        }
+        entry.deleted_at = Some(now);
+        Ok(())
+    }
+
+    /// Restore a soft-deleted entry
+    pub fn restore(&mut self, vocabulary: Vocabulary, id: u64) -> Result<(), KittyError> {
+        let (location, entry) = self.find_mut(vocabulary, id)?;
+        if entry.deleted_at.is_none() {
+            return Err(KittyError::InvalidInput(format!("{} {} is not deleted", vocabulary.label(), id)));
+        }
+        let text = entry.text.clone();
+        self.ensure_unique(vocabulary, &location, &text, Some(id))?;
+        let (_, entry) = self.find_mut(vocabulary, id)?;
+        entry.deleted_at = None;
+        Ok(())
+    }
+
+    /// Soft-delete every active template in a context, returning how many were deleted
+    pub fn delete_context(&mut self, context: &str, now: u64) -> Result<u32, KittyError> {
+        let entries = self
+            .templates
+            .get_mut(context)
+            .ok_or_else(|| KittyError::NotFound(format!("Unknown context '{}'", context)))?;
+        let mut deleted = 0;
+        for entry in entries.iter_mut().filter(|e| e.deleted_at.is_none()) {
+            entry.deleted_at = Some(now);
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+
+    /// Restore every deleted template in a context, returning how many were restored
+    /// Templates that duplicate an active one (such as one added after the delete) stay deleted.
+    pub fn restore_context(&mut self, context: &str) -> Result<u32, KittyError> {
+        let deleted: Vec<u64> = self
+            .templates
+            .get(context)
+            .ok_or_else(|| KittyError::NotFound(format!("Unknown context '{}'", context)))?
+            .iter()
+            .filter(|e| e.deleted_at.is_some())
+            .map(|e| e.id)
+            .collect();
+        let mut restored = 0;
+        for id in deleted {
+            if self.restore(Vocabulary::Template, id).is_ok() {
+                restored += 1;
+            }
+        }
+        Ok(restored)
+    }
+
+    /// Active (not deleted) entries under `key`, or None if the key has none
+    /// Only for templates, adjectives and phrases; see `word_entries` for words.
+    pub fn active_entries(&self, vocabulary: Vocabulary, key: &str) -> Option<Vec<WisdomEntry>> {
+        let entries: Vec<WisdomEntry> = self
+            .section(vocabulary)?
+            .get(key)?
+            .iter()
+            .filter(|e| e.deleted_at.is_none())
+            .cloned()
+            .collect();
+        if entries.is_empty() {
+            None
+        } else {
+            Some(entries)
+        }
+    }
+
+    /// Every key in a vocabulary with its number of active entries, sorted by name
+    pub fn key_counts(&self, vocabulary: Vocabulary) -> Vec<KeyCount> {
+        let mut counts: Vec<KeyCount> = self
+            .section(vocabulary)
+            .into_iter()
+            .flatten()
+            .map(|(key, entries)| {
+                KeyCount::new(key.clone(), entries.iter().filter(|e| e.deleted_at.is_none()).count() as u32)
+            })
+            .filter(|c| c.count > 0)
+            .collect();
+        counts.sort_by(|a, b| a.name.cmp(&b.name));
+        counts
+    }
+
+    /// Number of active entries under `key`
+    pub fn active_count(&self, vocabulary: Vocabulary, key: &str) -> u32 {
+        self.section(vocabulary)
+            .and_then(|section| section.get(key))
+            .map_or(0, |entries| entries.iter().filter(|e| e.deleted_at.is_none()).count() as u32)
+    }
+
+    /// A template by ID, deleted or not, with its context
+    pub fn template(&self, id: u64) -> Result<(String, WisdomEntry), KittyError> {
+        self.templates
+            .iter()
+            .find_map(|(context, entries)| entries.iter().find(|e| e.id == id).map(|e| (context.clone(), e.clone())))
+            .ok_or_else(|| KittyError::NotFound(format!("No {} with ID {}", Vocabulary::Template.label(), id)))
+    }
+
+    /// An active template by ID with its context; deleted templates aren't found
+    pub fn active_template(&self, id: u64) -> Result<(String, WisdomEntry), KittyError> {
+        match self.template(id)? {
+            (_, entry) if entry.deleted_at.is_some() => {
+                Err(KittyError::NotFound(format!("No {} with ID {}", Vocabulary::Template.label(), id)))
+            }
+            found => Ok(found),
+        }
+    }
+
+    /// Number of active entries in any vocabulary tagged with `tag`
+    pub fn tagged_count(&self, tag: &str) -> u32 {
+        Vocabulary::ALL
+            .iter()
+            .flat_map(|&vocabulary| self.sections(vocabulary))
+            .flat_map(|(_, section)| section.values().flatten())
+            .filter(|e| e.deleted_at.is_none() && e.tags.iter().any(|t| t == tag))
+            .count() as u32
+    }
+
+    /// Active templates in a context that may be served (weight above 0), or None if it has none
+    pub fn servable_templates(&self, context: &str) -> Option<Vec<WisdomEntry>> {
+        let entries: Vec<WisdomEntry> = self
+            .active_entries(Vocabulary::Template, context)?
+            .into_iter()
+            .filter(|e| e.weight > 0)
+            .collect();
+        if entries.is_empty() {
+            None
+        } else {
+            Some(entries)
+        }
+    }
+
+    /// Active templates in a context as WisdomTemplate records, or None if it has none
+    pub fn active_templates(&self, context: &str) -> Option<Vec<WisdomTemplate>> {
+        self.active_entries(Vocabulary::Template, context).map(|entries| {
+            entries.iter().map(|e| WisdomTemplate::from_entry(context, e)).collect()
+        })
+    }
+
+    /// One page of active templates in ascending ID order
+    /// `context` restricts the listing to one context; `filter` is a case-insensitive substring.
+    pub fn template_page(&self, context: Option<&str>, filter: Option<&str>, cursor: Option<u64>, page_size: u32) -> TemplatePage {
+        let filter = filter.map(str::to_lowercase);
+        let mut matches: Vec<WisdomTemplate> = self
+            .templates
+            .iter()
+            .filter(|(k, _)| context.is_none_or(|context| context == k.as_str()))
+            .flat_map(|(k, entries)| entries.iter().map(move |entry| (k, entry)))
+            .filter(|(_, entry)| entry.deleted_at.is_none())
+            .filter(|(_, entry)| cursor.is_none_or(|after| entry.id > after))
+            .filter(|(_, entry)| {
+                filter
+                    .as_deref()
+                    .is_none_or(|f| entry.text.to_lowercase().contains(f))
+            })
+            .map(|(k, entry)| WisdomTemplate::from_entry(k, entry))
+            .collect();
+        matches.sort_by_key(|template| template.id);
+
+        let page_size = page_size as usize;
+        let next_cursor = if matches.len() > page_size {
+            matches.truncate(page_size);
+            matches.last().map(|template| template.id)
+        } else {
+            None
+        };
+
+        TemplatePage { templates: matches, next_cursor }
+    }
+
+    /// Every soft-deleted entry, across all vocabularies
+    pub fn deleted_entries(&self) -> Vec<DeletedEntry> {
+        let mut deleted = Vec::new();
+        for vocabulary in Vocabulary::ALL {
+            for (dictionary, section) in self.sections(vocabulary) {
+                for (key, entries) in section {
+                    for entry in entries.iter().filter(|e| e.deleted_at.is_some()) {
+                        deleted.push(DeletedEntry {
+                            vocabulary,
+                            dictionary: dictionary.map(str::to_string),
+                            key: key.clone(),
+                            entry: entry.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        deleted.sort_by_key(|d| d.entry.id);
+        deleted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curator() -> Principal {
+        Principal::from_slice(&[7])
+    }
+
+    fn texts(entries: &[WisdomEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.text.as_str()).collect()
+    }
+
+    /// A store with templates "a" and "b" (IDs 0 and 1) under "general"
+    fn store() -> WisdomTemplateStore {
+        let templates = [("general".to_string(), vec!["a".to_string(), "b".to_string()])].into();
+        WisdomTemplateStore::from_text(templates, HashMap::new(), HashMap::new(), 0)
+    }
+
+    #[test]
+    fn add_update_delete_and_restore_an_entry() {
+        let mut store = store();
+        let id = store.add(Vocabulary::Template, "general".to_string(), "c".to_string(), curator(), 5).unwrap();
+        assert_eq!(id, 2);
+        assert_eq!(store.template(id).unwrap().1.author, Some(curator()));
+
+        store.update(Vocabulary::Template, id, "c2".to_string(), 6).unwrap();
+        let (context, entry) = store.template(id).unwrap();
+        assert_eq!((context.as_str(), entry.text.as_str(), entry.created_at, entry.updated_at), ("general", "c2", 5, 6));
+
+        store.delete(Vocabulary::Template, id, 7).unwrap();
+        assert_eq!(texts(&store.active_entries(Vocabulary::Template, "general").unwrap()), ["a", "b"]);
+        assert!(matches!(store.delete(Vocabulary::Template, id, 8), Err(KittyError::NotFound(_))));
+        assert_eq!(store.deleted_entries().len(), 1);
+
+        store.restore(Vocabulary::Template, id).unwrap();
+        assert_eq!(texts(&store.active_entries(Vocabulary::Template, "general").unwrap()), ["a", "b", "c2"]);
+        assert!(matches!(store.restore(Vocabulary::Template, id), Err(KittyError::InvalidInput(_))));
+        assert!(store.deleted_entries().is_empty());
+    }
+
+    #[test]
+    fn ids_belong_to_one_vocabulary() {
+        let mut store = store();
+        assert!(matches!(store.update(Vocabulary::Phrase, 0, "x".to_string(), 1), Err(KittyError::NotFound(_))));
+        assert!(matches!(store.delete(Vocabulary::Template, 99, 1), Err(KittyError::NotFound(_))));
+    }
+
+    #[test]
+    fn duplicates_are_rejected_under_the_same_key_only() {
+        let mut store = store();
+        let duplicate = store.add(Vocabulary::Template, "general".to_string(), "a".to_string(), curator(), 1);
+        assert!(matches!(duplicate, Err(KittyError::Duplicate(_))));
+        assert!(matches!(store.update(Vocabulary::Template, 1, "a".to_string(), 1), Err(KittyError::Duplicate(_))));
+        // Saving an entry's own text again is fine
+        assert!(store.update(Vocabulary::Template, 1, "b".to_string(), 1).is_ok());
+        assert!(store.add(Vocabulary::Template, "team".to_string(), "a".to_string(), curator(), 1).is_ok());
+
+        // A deleted entry doesn't block its text, but then can't be restored next to it
+        store.delete(Vocabulary::Template, 0, 2).unwrap();
+        let readded = store.add(Vocabulary::Template, "general".to_string(), "a".to_string(), curator(), 3).unwrap();
+        assert!(matches!(store.restore(Vocabulary::Template, 0), Err(KittyError::Duplicate(_))));
+        store.delete(Vocabulary::Template, readded, 4).unwrap();
+        assert!(store.restore(Vocabulary::Template, 0).is_ok());
+    }
+
+    #[test]
+    fn delete_and_restore_a_whole_context() {
+        let mut store = store();
+        store.delete(Vocabulary::Template, 1, 1).unwrap();
+        assert_eq!(store.delete_context("general", 2), Ok(1));
+        assert!(store.active_entries(Vocabulary::Template, "general").is_none());
+        assert_eq!(store.restore_context("general"), Ok(2));
+        assert_eq!(texts(&store.active_entries(Vocabulary::Template, "general").unwrap()), ["a", "b"]);
+        assert!(matches!(store.delete_context("nowhere", 3), Err(KittyError::NotFound(_))));
+        assert!(matches!(store.restore_context("nowhere"), Err(KittyError::NotFound(_))));
+    }
+
+    #[test]
+    fn restore_context_keeps_duplicates_deleted() {
+        let mut store = store();
+        store.delete_context("general", 1).unwrap();
+        let readded = store.add(Vocabulary::Template, "general".to_string(), "a".to_string(), curator(), 2).unwrap();
+
+        assert_eq!(store.restore_context("general"), Ok(1));
+        let active = store.active_entries(Vocabulary::Template, "general").unwrap();
+        assert_eq!(texts(&active), ["b", "a"]);
+        assert_eq!(active[1].id, readded);
+        assert_eq!(store.deleted_entries().iter().map(|d| d.entry.id).collect::<Vec<_>>(), [0]);
+    }
+
+    fn page_ids(page: &TemplatePage) -> Vec<u64> {
+        page.templates.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn key_counts_skip_deleted_entries_and_empty_keys() {
+        let mut store = store();
+        store.add(Vocabulary::Template, "team".to_string(), "t".to_string(), curator(), 1).unwrap();
+        store.delete(Vocabulary::Template, 0, 2).unwrap();
+
+        let counts: Vec<(String, u32)> = store.key_counts(Vocabulary::Template).into_iter().map(|c| (c.name, c.count)).collect();
+        assert_eq!(counts, [("general".to_string(), 1), ("team".to_string(), 1)]);
+        assert_eq!(store.active_count(Vocabulary::Template, "general"), 1);
+        assert_eq!(store.active_count(Vocabulary::Template, "nowhere"), 0);
+
+        store.delete_context("team", 3).unwrap();
+        assert_eq!(store.key_counts(Vocabulary::Template).len(), 1);
+    }
+
+    #[test]
+    fn template_pages_follow_the_cursor_in_id_order() {
+        let mut store = store();
+        for text in ["c", "d", "e"] {
+            store.add(Vocabulary::Template, "team".to_string(), text.to_string(), curator(), 1).unwrap();
+        }
+        store.delete(Vocabulary::Template, 3, 2).unwrap();
+
+        let first = store.template_page(None, None, None, 2);
+        assert_eq!(page_ids(&first), [0, 1]);
+        assert_eq!(first.next_cursor, Some(1));
+        let second = store.template_page(None, None, first.next_cursor, 2);
+        assert_eq!(page_ids(&second), [2, 4]);
+        assert_eq!(second.next_cursor, None);
+
+        // A page that exactly fills up is the last one
+        assert_eq!(store.template_page(None, None, Some(1), 2).next_cursor, None);
+        assert!(store.template_page(None, None, Some(4), 2).templates.is_empty());
+    }
+
+    #[test]
+    fn template_pages_filter_by_context_and_text() {
+        let mut store = store();
+        store.add(Vocabulary::Template, "team".to_string(), "Big A energy".to_string(), curator(), 1).unwrap();
+
+        assert_eq!(page_ids(&store.template_page(Some("team"), None, None, 10)), [2]);
+        assert_eq!(page_ids(&store.template_page(None, Some("a"), None, 10)), [0, 2]);
+        assert_eq!(page_ids(&store.template_page(Some("general"), Some("A"), None, 10)), [0]);
+        assert!(store.template_page(Some("nowhere"), None, None, 10).templates.is_empty());
+    }
+
+    #[test]
+    fn from_text_assigns_ids_in_key_order() {
+        let templates = [
+            ("team".to_string(), vec!["t".to_string()]),
+            ("general".to_string(), vec!["g1".to_string(), "g2".to_string()]),
+        ]
+        .into();
+        let phrases = [("Tranquil".to_string(), vec!["p".to_string()])].into();
+        let store = WisdomTemplateStore::from_text(templates, HashMap::new(), phrases, 9);
+
+        let ids = |vocabulary, key| -> Vec<u64> { store.active_entries(vocabulary, key).unwrap().iter().map(|e| e.id).collect() };
+        assert_eq!(ids(Vocabulary::Template, "general"), [0, 1]);
+        assert_eq!(ids(Vocabulary::Template, "team"), [2]);
+        assert_eq!(ids(Vocabulary::Phrase, "Tranquil"), [3]);
+        assert_eq!(store.next_id, 4);
+
+        let entry = &store.active_entries(Vocabulary::Phrase, "Tranquil").unwrap()[0];
+        assert_eq!((entry.author, entry.created_at, entry.weight, entry.usage_count), (None, 9, DEFAULT_WEIGHT, 0));
+    }
+
+    #[test]
+    fn tags_are_bounded_and_counted_across_vocabularies() {
+        let mut store = store();
+        let phrase = store.add(Vocabulary::Phrase, "Tranquil".to_string(), "p".to_string(), curator(), 1).unwrap();
+        store.set_tags(Vocabulary::Template, 0, vec!["calm".to_string()], 2).unwrap();
+        store.set_tags(Vocabulary::Phrase, phrase, vec!["calm".to_string(), "soft".to_string()], 2).unwrap();
+        assert_eq!(store.template(0).unwrap().1.updated_at, 2);
+        assert_eq!(store.tagged_count("calm"), 2);
+        assert_eq!(store.tagged_count("soft"), 1);
+
+        store.delete(Vocabulary::Phrase, phrase, 3).unwrap();
+        assert_eq!(store.tagged_count("calm"), 1);
+
+        let too_many = (0..=MAX_TAGS).map(|i| format!("t{}", i)).collect();
+        assert!(matches!(store.set_tags(Vocabulary::Template, 0, too_many, 4), Err(KittyError::InvalidInput(_))));
+        assert_eq!(store.template(0).unwrap().1.tags, ["calm"]);
+    }
+
+    #[test]
+    fn usage_is_recorded_per_entry() {
+        let mut store = store();
+        store.record_usage(Vocabulary::Template, 1, 10);
+        store.record_usage(Vocabulary::Template, 1, 20);
+        store.record_usage(Vocabulary::Template, 99, 30);
+
+        let (_, entry) = store.template(1).unwrap();
+        assert_eq!((entry.usage_count, entry.last_used_at), (2, Some(20)));
+        assert_eq!(store.template(0).unwrap().1.usage_count, 0);
+    }
+
+    #[test]
+    fn active_template_hides_deleted_ones() {
+        let mut store = store();
+        store.delete(Vocabulary::Template, 0, 1).unwrap();
+        assert!(store.template(0).is_ok());
+        assert!(matches!(store.active_template(0), Err(KittyError::NotFound(_))));
+        assert_eq!(store.active_template(1).unwrap().0, "general");
+    }
+
+    #[test]
+    fn dictionaries_are_defined_once() {
+        let mut store = store();
+        store.define_dictionary("animal".to_string(), SlotKey::Unkeyed).unwrap();
+        assert!(matches!(store.define_dictionary("animal".to_string(), SlotKey::Mood), Err(KittyError::Duplicate(_))));
+        assert!(matches!(store.dictionary("plant"), Err(KittyError::NotFound(_))));
+        let missing = store.add_word("plant", ANY_KEY.to_string(), "fern".to_string(), curator(), 1);
+        assert!(matches!(missing, Err(KittyError::NotFound(_))));
+    }
+
+    #[test]
+    fn keyed_words_fall_back_to_any_key() {
+        let mut store = store();
+        store.define_dictionary("snack".to_string(), SlotKey::Mood).unwrap();
+        store.add_word("snack", ANY_KEY.to_string(), "kibble".to_string(), curator(), 1).unwrap();
+        let tuna = store.add_word("snack", "Playful".to_string(), "tuna".to_string(), curator(), 1).unwrap();
+
+        assert_eq!(texts(&store.active_words("snack", "Playful")), ["tuna"]);
+        assert_eq!(texts(&store.active_words("snack", "Tranquil")), ["kibble"]);
+        assert!(store.active_words("nothing", "Playful").is_empty());
+        assert!(store.word_entries("snack", "Tranquil").unwrap().is_empty());
+
+        store.delete(Vocabulary::Word, tuna, 2).unwrap();
+        assert_eq!(texts(&store.active_words("snack", "Playful")), ["kibble"]);
+        assert_eq!(store.deleted_entries()[0].dictionary.as_deref(), Some("snack"));
+    }
+
+    #[test]
+    fn words_are_unique_per_dictionary_and_key() {
+        let mut store = store();
+        store.define_dictionary("animal".to_string(), SlotKey::Unkeyed).unwrap();
+        store.define_dictionary("pet".to_string(), SlotKey::Unkeyed).unwrap();
+        store.add_word("animal", ANY_KEY.to_string(), "cat".to_string(), curator(), 1).unwrap();
+
+        let duplicate = store.add_word("animal", ANY_KEY.to_string(), "cat".to_string(), curator(), 1);
+        assert!(matches!(duplicate, Err(KittyError::Duplicate(_))));
+        assert!(store.add_word("pet", ANY_KEY.to_string(), "cat".to_string(), curator(), 1).is_ok());
+    }
+
+    #[test]
+    fn dictionary_infos_count_active_words() {
+        let mut store = store();
+        store.define_dictionary("pet".to_string(), SlotKey::State).unwrap();
+        store.define_dictionary("animal".to_string(), SlotKey::Unkeyed).unwrap();
+        store.add_word("pet", "Entangled".to_string(), "cat".to_string(), curator(), 1).unwrap();
+        let dog = store.add_word("pet", "Folded".to_string(), "dog".to_string(), curator(), 1).unwrap();
+        store.delete(Vocabulary::Word, dog, 2).unwrap();
+
+        let infos: Vec<(String, SlotKey, u32)> =
+            store.dictionary_infos().into_iter().map(|i| (i.name, i.keyed_by, i.word_count)).collect();
+        assert_eq!(infos, [("animal".to_string(), SlotKey::Unkeyed, 0), ("pet".to_string(), SlotKey::State, 1)]);
+    }
+
+    #[test]
+    fn weights_are_bounded_and_zero_is_allowed() {
+        let mut store = store();
+        assert!(matches!(store.set_weight(0, MAX_WEIGHT + 1, 2), Err(KittyError::InvalidInput(_))));
+        store.set_weight(0, MAX_WEIGHT, 2).unwrap();
+        store.set_weight(1, 0, 3).unwrap();
+        assert_eq!(store.template(1).unwrap().1.weight, 0);
+        assert!(matches!(store.set_weight(99, 1, 4), Err(KittyError::NotFound(_))));
+    }
+
+    #[test]
+    fn templates_with_weight_zero_are_never_servable() {
+        let mut store = store();
+        store.set_weight(1, 0, 2).unwrap();
+        assert_eq!(texts(&store.servable_templates("general").unwrap()), ["a"]);
+        assert_eq!(store.active_entries(Vocabulary::Template, "general").unwrap().len(), 2);
+
+        store.set_weight(0, 0, 3).unwrap();
+        assert!(store.servable_templates("general").is_none());
+        assert!(store.servable_templates("missing").is_none());
+    }
+}
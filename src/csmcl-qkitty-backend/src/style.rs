@@ -0,0 +1,195 @@
+// Wisdom style: the goal, register, length and keywords a wisdom request asks for
+//
+// Goals, registers and keywords are matched against template tags, so curators opt a
+// template in with `set_template_tags` (e.g. "condolence" or "formal"). Keywords are
+// also matched against the tags of quantum adjectives, zen phrases and slot words.
+// Length is judged on the rendered text. None of them is a hard filter: when nothing
+// fits, the closest entry is used and the response says which preferences were honored.
+use candid::CandidType;
+use serde::Deserialize;
+
+use crate::store::WisdomEntry;
+
+/// Rendered wisdom up to this many characters is short
+const SHORT_MAX_CHARS: usize = 140;
+
+/// Rendered wisdom up to this many characters is medium (longer is long)
+const MEDIUM_MAX_CHARS: usize = 220;
+
+/// Goal is what the wisdom is for
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Goal {
+    BirthdayPresent,
+    MeetingOpener,
+    Motivation,
+    Condolence,
+    Celebration,
+    Gratitude,
+}
+
+impl Goal {
+    /// Template tag that marks a template as suited to this goal
+    pub fn tag(self) -> &'static str {
+        match self {
+            Goal::BirthdayPresent => "birthday-present",
+            Goal::MeetingOpener => "meeting-opener",
+            Goal::Motivation => "motivation",
+            Goal::Condolence => "condolence",
+            Goal::Celebration => "celebration",
+            Goal::Gratitude => "gratitude",
+        }
+    }
+}
+
+/// Register is the tone the wisdom is written in
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    Friendly,
+    Corporate,
+    Formal,
+}
+
+impl Register {
+    /// Template tag that marks a template as written in this register
+    pub fn tag(self) -> &'static str {
+        match self {
+            Register::Friendly => "friendly",
+            Register::Corporate => "corporate",
+            Register::Formal => "formal",
+        }
+    }
+}
+
+/// Length is how long rendered wisdom reads
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Length {
+    Short,  // Up to 140 characters
+    Medium, // Up to 220 characters
+    Long,   // Anything longer
+}
+
+impl Length {
+    /// The length bucket of rendered `text`
+    pub fn of(text: &str) -> Self {
+        match text.chars().count() {
+            n if n <= SHORT_MAX_CHARS => Length::Short,
+            n if n <= MEDIUM_MAX_CHARS => Length::Medium,
+            _ => Length::Long,
+        }
+    }
+}
+
+/// Preferences are the validated style choices of a wisdom request
+#[derive(Default)]
+pub struct Preferences {
+    pub goal: Option<Goal>,
+    pub register: Option<Register>,
+    pub length: Option<Length>,
+    pub keywords: Vec<String>, // Lowercase, as template tags are
+}
+
+/// Fit is which of the requested preferences a set of tags satisfies
+#[derive(Default)]
+pub struct Fit {
+    pub goal: bool,
+    pub register: bool,
+    pub keywords: Vec<String>, // Requested keywords among the tags, in request order
+}
+
+impl Preferences {
+    /// How well an entry tagged with `tags` fits these preferences
+    pub fn fit(&self, tags: &[String]) -> Fit {
+        let tagged = |tag: &str| tags.iter().any(|t| t == tag);
+        Fit {
+            goal: self.goal.is_some_and(|goal| tagged(goal.tag())),
+            register: self.register.is_some_and(|register| tagged(register.tag())),
+            keywords: self.keywords.iter().filter(|k| tagged(k)).cloned().collect(),
+        }
+    }
+
+    /// The entries tagged with the most requested keywords (all of them if none match any)
+    pub fn best_keyword_matches<'a>(&self, entries: &'a [WisdomEntry]) -> Vec<&'a WisdomEntry> {
+        let matches = |entry: &WisdomEntry| self.keywords.iter().filter(|k| entry.tags.contains(k)).count();
+        let best = entries.iter().map(matches).max().unwrap_or(0);
+        entries.iter().filter(|e| matches(e) == best).collect()
+    }
+
+    /// The requested keywords found in `honored`, in request order and without repeats
+    pub fn honored_keywords(&self, honored: &[String]) -> Vec<String> {
+        self.keywords.iter().filter(|k| honored.contains(k)).cloned().collect()
+    }
+}
+
+impl Fit {
+    /// Ranking key: the goal matters most, then the register, then how many keywords match
+    pub fn score(&self) -> (bool, bool, usize) {
+        (self.goal, self.register, self.keywords.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    fn entry(id: u64, tags: &[&str]) -> WisdomEntry {
+        WisdomEntry {
+            id,
+            text: format!("t{}", id),
+            tags: strings(tags),
+            weight: 1,
+            author: None,
+            created_at: 0,
+            updated_at: 0,
+            usage_count: 0,
+            last_used_at: None,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn length_buckets_follow_the_character_thresholds() {
+        assert_eq!(Length::of(""), Length::Short);
+        assert_eq!(Length::of(&"a".repeat(SHORT_MAX_CHARS)), Length::Short);
+        assert_eq!(Length::of(&"a".repeat(SHORT_MAX_CHARS + 1)), Length::Medium);
+        assert_eq!(Length::of(&"a".repeat(MEDIUM_MAX_CHARS)), Length::Medium);
+        assert_eq!(Length::of(&"a".repeat(MEDIUM_MAX_CHARS + 1)), Length::Long);
+        // Characters, not bytes
+        assert_eq!(Length::of(&"ü".repeat(SHORT_MAX_CHARS)), Length::Short);
+    }
+
+    #[test]
+    fn fit_ranks_goal_over_register_over_keywords() {
+        let preferences = Preferences {
+            goal: Some(Goal::Condolence),
+            register: Some(Register::Formal),
+            length: None,
+            keywords: strings(&["rain", "tea"]),
+        };
+        let goal = preferences.fit(&strings(&["condolence"]));
+        let register = preferences.fit(&strings(&["formal", "rain", "tea"]));
+        let keywords = preferences.fit(&strings(&["tea", "rain", "friendly"]));
+
+        assert_eq!(goal.score(), (true, false, 0));
+        assert_eq!(register.score(), (false, true, 2));
+        assert_eq!(keywords.keywords, ["rain", "tea"]);
+        assert!(goal.score() > register.score());
+        assert!(register.score() > keywords.score());
+        assert_eq!(Preferences::default().fit(&strings(&["formal"])).score(), (false, false, 0));
+    }
+
+    #[test]
+    fn best_keyword_matches_keep_the_entries_matching_the_most_keywords() {
+        let preferences = Preferences { keywords: strings(&["rain", "tea"]), ..Preferences::default() };
+        let entries = [entry(0, &["rain"]), entry(1, &["tea", "rain"]), entry(2, &[]), entry(3, &["rain", "tea"])];
+        let ids = |best: Vec<&WisdomEntry>| best.iter().map(|e| e.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(preferences.best_keyword_matches(&entries)), [1, 3]);
+        assert_eq!(ids(preferences.best_keyword_matches(&entries[2..3])), [2]);
+        assert!(preferences.best_keyword_matches(&[]).is_empty());
+        assert_eq!(preferences.honored_keywords(&strings(&["tea", "moon"])), ["tea"]);
+    }
+}
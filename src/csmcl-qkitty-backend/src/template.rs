@@ -0,0 +1,965 @@
+// Wisdom template language
+//
+// A template is literal text with `{placeholder}` slots, e.g.
+//   "The {quantum} field around {kitty} hums. {zen}"
+// Besides the built-in placeholders, any slot dictionary defined in the store can be
+// referenced by name (e.g. `{animal}`).
+//
+// Wisdom may be a gift: `{name}` is the recipient (or "you"), and `{sender}`,
+// `{relationship}`, `{age}` and `{age_ordinal}` describe the occasion. The pronoun
+// placeholders `{they}`, `{them}`, `{their}`, `{theirs}` and `{themselves}` read as
+// "you/your/..." when the wisdom is addressed to the reader and as neutral
+// "they/their/..." when it is about a named recipient, so one template serves both.
+//
+// Calendar placeholders describe the caller's local date: `{weekday}` ("Monday"),
+// `{date}` ("October 18, 2026"), `{day}` ("18th"), `{month}`, `{year}`, `{season}`
+// and `{time_of_day}` ("morning", "afternoon", "evening" or "night").
+//
+// Templates also support sections that adapt the text:
+//   {#if energy>7}...{#else}...{/if}   energy or age comparisons (>, >=, <, <=, =, !=),
+//                                      `state=Name` / `mood!=Name`, `weekday=Monday`,
+//                                      `season=winter`, `time_of_day!=night`, or a bare
+//                                      `recipient`, `sender`, `relationship` or `age`
+//                                      (true when the request supplied it)
+//   {#mood Playful|Tranquil}...{/mood} only when the zen mood is one of these
+//   {#state Entangled}...{/state}      only when the quantum state is one of these
+//   {one|two|three}                    one alternative, picked at render time
+// Sections nest, and `{#mood}`/`{#state}` sections may also have an `{#else}`.
+//
+// Templates are parsed once into a tree and rendered in a single pass, so a
+// substituted value is never itself scanned for placeholders. Literal braces are
+// written doubled: `{{` renders as `{` and `}}` as `}`.
+//
+// Malformed sections are rejected when a template is parsed, and references to
+// dictionaries that don't exist are rejected (via `check_dictionaries`) when a template
+// is added or edited, so typos like `{qantum}` never reach users.
+use crate::calendar::{self, Moment};
+use crate::error::KittyError;
+
+/// Placeholder is a named slot that is filled in when wisdom is generated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Placeholder {
+    Quantum,            // An adjective for the current quantum state
+    Zen,                // A phrase for the current zen mood
+    Name,               // The person receiving the wisdom
+    Kitty,              // The kitty's name
+    Sender,             // The person giving the wisdom
+    Relationship,       // How the sender knows the recipient
+    Age,                // The recipient's age ("30")
+    AgeOrdinal,         // The recipient's age as an ordinal ("30th")
+    Pronoun(Pronoun),   // A pronoun for the recipient
+    Weekday,            // "Monday"
+    Date,               // "October 18, 2026"
+    Day,                // Day of the month as an ordinal ("18th")
+    Month,              // "October"
+    Year,               // "2026"
+    Season,             // "autumn"
+    TimeOfDay,          // "morning", "afternoon", "evening" or "night"
+    Dictionary(String), // A word from the named slot dictionary
+}
+
+/// Pronoun forms, filled as second person ("you") or neutral third person ("they")
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pronoun {
+    Subject,    // you / they
+    Object,     // you / them
+    Possessive, // your / their
+    Absolute,   // yours / theirs
+    Reflexive,  // yourself / themselves
+}
+
+impl Pronoun {
+    /// The pronoun addressed to the reader, or about someone else
+    pub fn form(self, second_person: bool) -> &'static str {
+        match (self, second_person) {
+            (Pronoun::Subject, true) | (Pronoun::Object, true) => "you",
+            (Pronoun::Possessive, true) => "your",
+            (Pronoun::Absolute, true) => "yours",
+            (Pronoun::Reflexive, true) => "yourself",
+            (Pronoun::Subject, false) => "they",
+            (Pronoun::Object, false) => "them",
+            (Pronoun::Possessive, false) => "their",
+            (Pronoun::Absolute, false) => "theirs",
+            (Pronoun::Reflexive, false) => "themselves",
+        }
+    }
+}
+
+impl Placeholder {
+    const BUILT_IN: [Placeholder; 20] = [
+        Placeholder::Quantum,
+        Placeholder::Zen,
+        Placeholder::Name,
+        Placeholder::Kitty,
+        Placeholder::Sender,
+        Placeholder::Relationship,
+        Placeholder::Age,
+        Placeholder::AgeOrdinal,
+        Placeholder::Pronoun(Pronoun::Subject),
+        Placeholder::Pronoun(Pronoun::Object),
+        Placeholder::Pronoun(Pronoun::Possessive),
+        Placeholder::Pronoun(Pronoun::Absolute),
+        Placeholder::Pronoun(Pronoun::Reflexive),
+        Placeholder::Weekday,
+        Placeholder::Date,
+        Placeholder::Day,
+        Placeholder::Month,
+        Placeholder::Year,
+        Placeholder::Season,
+        Placeholder::TimeOfDay,
+    ];
+
+    fn name(&self) -> &str {
+        match self {
+            Placeholder::Quantum => "quantum",
+            Placeholder::Zen => "zen",
+            Placeholder::Name => "name",
+            Placeholder::Kitty => "kitty",
+            Placeholder::Sender => "sender",
+            Placeholder::Relationship => "relationship",
+            Placeholder::Age => "age",
+            Placeholder::AgeOrdinal => "age_ordinal",
+            Placeholder::Pronoun(Pronoun::Subject) => "they",
+            Placeholder::Pronoun(Pronoun::Object) => "them",
+            Placeholder::Pronoun(Pronoun::Possessive) => "their",
+            Placeholder::Pronoun(Pronoun::Absolute) => "theirs",
+            Placeholder::Pronoun(Pronoun::Reflexive) => "themselves",
+            Placeholder::Weekday => "weekday",
+            Placeholder::Date => "date",
+            Placeholder::Day => "day",
+            Placeholder::Month => "month",
+            Placeholder::Year => "year",
+            Placeholder::Season => "season",
+            Placeholder::TimeOfDay => "time_of_day",
+            Placeholder::Dictionary(name) => name,
+        }
+    }
+
+    /// A built-in placeholder by name, or a dictionary reference if `name` could name one
+    fn from_name(name: &str) -> Option<Self> {
+        if let Some(built_in) = Self::BUILT_IN.into_iter().find(|p| p.name() == name) {
+            return Some(built_in);
+        }
+        let is_key = !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+        is_key.then(|| Placeholder::Dictionary(name.to_string()))
+    }
+
+    /// True if `name` is taken by a built-in placeholder
+    pub fn is_built_in(name: &str) -> bool {
+        Self::BUILT_IN.iter().any(|p| p.name() == name)
+    }
+}
+
+/// RenderContext supplies the values and state a template is rendered against
+pub trait RenderContext {
+    /// The text for a placeholder
+    fn fill(&mut self, placeholder: &Placeholder) -> String;
+    /// Pick one of `options` alternatives (returns an index below `options`)
+    fn choose(&mut self, options: usize) -> usize;
+    fn energy_level(&self) -> u8;
+    fn quantum_state(&self) -> &str;
+    fn zen_mood(&self) -> &str;
+    /// The recipient's age, if the request gave one
+    fn age(&self) -> Option<u8>;
+    /// True if the request supplied `detail`
+    fn knows(&self, detail: Detail) -> bool;
+    /// The caller's local date and time
+    fn moment(&self) -> &Moment;
+}
+
+/// Calendar facts that conditions can test
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CalendarField {
+    Weekday,
+    Season,
+    TimeOfDay,
+}
+
+impl CalendarField {
+    fn name(self) -> &'static str {
+        match self {
+            CalendarField::Weekday => "weekday",
+            CalendarField::Season => "season",
+            CalendarField::TimeOfDay => "time_of_day",
+        }
+    }
+
+    /// Every value the field can take, in canonical spelling
+    fn values(self) -> &'static [&'static str] {
+        match self {
+            CalendarField::Weekday => &calendar::WEEKDAYS,
+            CalendarField::Season => &calendar::SEASONS,
+            CalendarField::TimeOfDay => &calendar::TIMES_OF_DAY,
+        }
+    }
+
+    fn value_at(self, moment: &Moment) -> &'static str {
+        match self {
+            CalendarField::Weekday => moment.weekday(),
+            CalendarField::Season => moment.season(),
+            CalendarField::TimeOfDay => moment.time_of_day(),
+        }
+    }
+}
+
+/// Optional details of a wisdom request that templates can test for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Detail {
+    Recipient,
+    Sender,
+    Relationship,
+    Age,
+}
+
+/// Numbers that conditions can compare
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Measure {
+    Energy,
+    Age,
+}
+
+/// Comparison operators for number conditions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    fn holds(self, left: u8, right: u8) -> bool {
+        match self {
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+        }
+    }
+}
+
+/// Condition guards a section of a template
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Condition {
+    Compare(Measure, Comparison, u8),           // Never holds if the measure is unknown
+    State(Vec<String>),                         // The quantum state is one of these
+    Mood(Vec<String>),                          // The zen mood is one of these
+    Known(Detail),                              // The request supplied this detail
+    Calendar(CalendarField, Vec<&'static str>), // The local weekday, season or time of day is one of these
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    fn holds(&self, ctx: &dyn RenderContext) -> bool {
+        match self {
+            Condition::Compare(measure, comparison, value) => {
+                let actual = match measure {
+                    Measure::Energy => Some(ctx.energy_level()),
+                    Measure::Age => ctx.age(),
+                };
+                actual.is_some_and(|actual| comparison.holds(actual, *value))
+            }
+            Condition::Known(detail) => ctx.knows(*detail),
+            Condition::Calendar(field, values) => values.contains(&field.value_at(ctx.moment())),
+            Condition::State(names) => names.iter().any(|n| n == ctx.quantum_state()),
+            Condition::Mood(names) => names.iter().any(|n| n == ctx.zen_mood()),
+            Condition::Not(inner) => !inner.holds(ctx),
+        }
+    }
+}
+
+/// Node is one piece of a parsed template
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Literal(String),
+    Slot { placeholder: Placeholder, column: usize },
+    Variants(Vec<String>),
+    Section { condition: Condition, then: Vec<Node>, otherwise: Vec<Node> },
+}
+
+/// Tag is the content of a `{...}` group, classified
+#[derive(Debug)]
+enum Tag {
+    Slot(Placeholder),
+    Variants(Vec<String>),
+    Open { block: &'static str, condition: Condition },
+    Else,
+    Close(String),
+}
+
+/// Token is a lexed piece of template text; tags keep their character position for errors
+#[derive(Debug)]
+enum Token {
+    Literal(String),
+    Tag { column: usize, tag: Tag },
+}
+
+/// Template is a parsed wisdom template, ready to render
+#[derive(Clone, Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    /// Parse template text, rejecting malformed placeholders and sections
+    pub fn parse(text: &str) -> Result<Self, KittyError> {
+        let mut tokens = tokenize(text)?.into_iter().peekable();
+        let (nodes, end) = parse_nodes(&mut tokens)?;
+        match end {
+            End::Eof => Ok(Self { nodes }),
+            End::Else(column) => Err(invalid(column, "'{#else}' outside a section")),
+            End::Close(column, name) => Err(invalid(column, &format!("'{{/{}}}' without a matching opening tag", name))),
+        }
+    }
+
+    /// Parse template text, treating text that isn't a valid template as a literal
+    /// Used for templates stored before validation existed, so they still render
+    pub fn parse_lenient(text: &str) -> Self {
+        Self::parse(text).unwrap_or_else(|_| Self { nodes: vec![Node::Literal(text.to_string())] })
+    }
+
+    /// Render the template against `ctx`
+    pub fn render(&self, ctx: &mut dyn RenderContext) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, ctx, &mut out);
+        out
+    }
+
+    /// Quantum state names referenced by conditions
+    pub fn referenced_states(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        collect_names(&self.nodes, &mut |condition| {
+            if let Condition::State(states) = condition {
+                names.extend(states.iter().cloned());
+            }
+        });
+        names
+    }
+
+    /// Reject references to slot dictionaries that aren't in `known`
+    pub fn check_dictionaries(&self, known: &[String]) -> Result<(), KittyError> {
+        let mut unknown = None;
+        visit_slots(&self.nodes, &mut |placeholder, column| {
+            if let Placeholder::Dictionary(name) = placeholder {
+                if unknown.is_none() && !known.contains(name) {
+                    unknown = Some((column, name.clone()));
+                }
+            }
+        });
+        match unknown {
+            Some((column, name)) => Err(invalid(column, &unknown_placeholder_message(&name, known))),
+            None => Ok(()),
+        }
+    }
+
+    /// Zen mood names referenced by conditions
+    pub fn referenced_moods(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        collect_names(&self.nodes, &mut |condition| {
+            if let Condition::Mood(moods) = condition {
+                names.extend(moods.iter().cloned());
+            }
+        });
+        names
+    }
+}
+
+/// An English ordinal for `n`: 1st, 2nd, 3rd, 4th, 11th, 21st...
+pub fn ordinal(n: u32) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// Check that `text` is a valid template
+pub fn validate(text: &str) -> Result<Template, KittyError> {
+    Template::parse(text)
+}
+
+fn render_nodes(nodes: &[Node], ctx: &mut dyn RenderContext, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Literal(text) => out.push_str(text),
+            Node::Slot { placeholder, .. } => out.push_str(&ctx.fill(placeholder)),
+            Node::Variants(options) => {
+                let index = ctx.choose(options.len()).min(options.len() - 1);
+                out.push_str(&options[index]);
+            }
+            Node::Section { condition, then, otherwise } => {
+                let branch = if condition.holds(ctx) { then } else { otherwise };
+                render_nodes(branch, ctx, out);
+            }
+        }
+    }
+}
+
+fn visit_slots(nodes: &[Node], visit: &mut dyn FnMut(&Placeholder, usize)) {
+    for node in nodes {
+        match node {
+            Node::Slot { placeholder, column } => visit(placeholder, *column),
+            Node::Section { then, otherwise, .. } => {
+                visit_slots(then, visit);
+                visit_slots(otherwise, visit);
+            }
+            Node::Literal(_) | Node::Variants(_) => {}
+        }
+    }
+}
+
+fn collect_names(nodes: &[Node], visit: &mut dyn FnMut(&Condition)) {
+    for node in nodes {
+        if let Node::Section { condition, then, otherwise } = node {
+            let mut condition = condition;
+            while let Condition::Not(inner) = condition {
+                condition = inner;
+            }
+            visit(condition);
+            collect_names(then, visit);
+            collect_names(otherwise, visit);
+        }
+    }
+}
+
+/// Split template text into literals and classified tags
+fn tokenize(text: &str) -> Result<Vec<Token>, KittyError> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut chars = text.chars().enumerate().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let column = index + 1;
+        match c {
+            '{' if chars.peek().is_some_and(|&(_, next)| next == '{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek().is_some_and(|&(_, next)| next == '}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut body = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some((_, '{')) | None => {
+                            return Err(invalid(column, "'{' is never closed (write '{{' for a literal brace)"))
+                        }
+                        Some((_, c)) => body.push(c),
+                    }
+                }
+                if !literal.is_empty() {
+                    tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                }
+                tokens.push(Token::Tag { column, tag: classify(column, &body)? });
+            }
+            '}' => return Err(invalid(column, "unmatched '}' (write '}}' for a literal brace)")),
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+
+    Ok(tokens)
+}
+
+/// Work out what a `{...}` group means
+fn classify(column: usize, body: &str) -> Result<Tag, KittyError> {
+    if let Some(directive) = body.strip_prefix('#') {
+        let (keyword, argument) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+        let argument = argument.trim();
+        return match keyword {
+            "if" => Ok(Tag::Open { block: "if", condition: parse_condition(column, argument)? }),
+            "mood" => Ok(Tag::Open { block: "mood", condition: Condition::Mood(name_list(column, "mood", argument)?) }),
+            "state" => Ok(Tag::Open { block: "state", condition: Condition::State(name_list(column, "state", argument)?) }),
+            "else" if argument.is_empty() => Ok(Tag::Else),
+            _ => Err(invalid(column, &format!(
+                "unknown section '{{#{}}}' (known: {{#if ...}}, {{#mood ...}}, {{#state ...}}, {{#else}})",
+                directive
+            ))),
+        };
+    }
+    if let Some(name) = body.strip_prefix('/') {
+        return Ok(Tag::Close(name.trim().to_string()));
+    }
+    if body.contains('|') {
+        return Ok(Tag::Variants(body.split('|').map(str::to_string).collect()));
+    }
+
+    let name = body.trim();
+    Placeholder::from_name(name)
+        .map(Tag::Slot)
+        .ok_or_else(|| invalid(column, &unknown_placeholder_message(name, &[])))
+}
+
+/// Parse the condition of an `{#if ...}` tag
+fn parse_condition(column: usize, text: &str) -> Result<Condition, KittyError> {
+    const OPERATORS: [(&str, Comparison); 7] = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        ("!=", Comparison::NotEqual),
+        ("==", Comparison::Equal),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equal),
+    ];
+
+    let detail = match text.trim() {
+        "recipient" => Some(Detail::Recipient),
+        "sender" => Some(Detail::Sender),
+        "relationship" => Some(Detail::Relationship),
+        "age" => Some(Detail::Age),
+        _ => None,
+    };
+    if let Some(detail) = detail {
+        return Ok(Condition::Known(detail));
+    }
+
+    let (subject, comparison, value) = OPERATORS
+        .iter()
+        .find_map(|(symbol, comparison)| {
+            text.split_once(symbol).map(|(subject, value)| (subject.trim(), *comparison, value.trim()))
+        })
+        .ok_or_else(|| {
+            invalid(column, "'{#if}' needs a condition like 'energy>7', 'mood=Playful', 'age>=18' or 'sender'")
+        })?;
+
+    match subject {
+        "energy" | "age" => {
+            let value = value
+                .parse::<u8>()
+                .map_err(|_| invalid(column, &format!("'{}' is not a valid {} (expected a number)", value, subject)))?;
+            let measure = if subject == "energy" { Measure::Energy } else { Measure::Age };
+            Ok(Condition::Compare(measure, comparison, value))
+        }
+        "state" | "mood" | "weekday" | "season" | "time_of_day" => {
+            let names = name_list(column, subject, value)?;
+            let condition = match subject {
+                "state" => Condition::State(names),
+                "mood" => Condition::Mood(names),
+                "weekday" => calendar_condition(column, CalendarField::Weekday, names)?,
+                "season" => calendar_condition(column, CalendarField::Season, names)?,
+                _ => calendar_condition(column, CalendarField::TimeOfDay, names)?,
+            };
+            match comparison {
+                Comparison::Equal => Ok(condition),
+                Comparison::NotEqual => Ok(Condition::Not(Box::new(condition))),
+                _ => Err(invalid(column, &format!("{} can only be compared with '=' or '!='", subject))),
+            }
+        }
+        _ => Err(invalid(column, &format!(
+            "unknown condition subject '{}' (known: energy, age, state, mood, weekday, season, time_of_day)",
+            subject
+        ))),
+    }
+}
+
+/// A calendar condition, matching values case-insensitively against the field's canonical spellings
+fn calendar_condition(column: usize, field: CalendarField, names: Vec<String>) -> Result<Condition, KittyError> {
+    let values = names
+        .iter()
+        .map(|name| {
+            field
+                .values()
+                .iter()
+                .find(|value| value.eq_ignore_ascii_case(name))
+                .copied()
+                .ok_or_else(|| {
+                    invalid(column, &format!(
+                        "'{}' is not a {} (expected one of: {})",
+                        name,
+                        field.name(),
+                        field.values().join(", ")
+                    ))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Condition::Calendar(field, values))
+}
+
+/// Parse `Name|Name|...` in a mood or state condition
+fn name_list(column: usize, subject: &str, text: &str) -> Result<Vec<String>, KittyError> {
+    let names: Vec<String> = text.split('|').map(|n| n.trim().to_string()).collect();
+    if names.iter().any(String::is_empty) {
+        return Err(invalid(column, &format!("'{}' condition needs one or more names separated by '|'", subject)));
+    }
+    Ok(names)
+}
+
+/// How a run of nodes ended
+enum End {
+    Eof,
+    Else(usize),
+    Close(usize, String),
+}
+
+/// Parse nodes until the end of input or a tag that ends the current section
+fn parse_nodes(tokens: &mut std::iter::Peekable<std::vec::IntoIter<Token>>) -> Result<(Vec<Node>, End), KittyError> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Literal(text) => nodes.push(Node::Literal(text)),
+            Token::Tag { column, tag: Tag::Slot(placeholder) } => nodes.push(Node::Slot { placeholder, column }),
+            Token::Tag { tag: Tag::Variants(options), .. } => nodes.push(Node::Variants(options)),
+            Token::Tag { column, tag: Tag::Else } => return Ok((nodes, End::Else(column))),
+            Token::Tag { column, tag: Tag::Close(name) } => return Ok((nodes, End::Close(column, name))),
+            Token::Tag { column, tag: Tag::Open { block, condition } } => {
+                let (then, mut end) = parse_nodes(tokens)?;
+                let mut otherwise = Vec::new();
+                if let End::Else(_) = end {
+                    let (nodes, after_else) = parse_nodes(tokens)?;
+                    otherwise = nodes;
+                    end = after_else;
+                }
+                match end {
+                    End::Close(_, name) if name == block => {}
+                    End::Close(close_column, name) => {
+                        return Err(invalid(close_column, &format!("'{{/{}}}' closes a '{{#{}}}' section", name, block)))
+                    }
+                    End::Else(else_column) => return Err(invalid(else_column, "a section can only have one '{#else}'")),
+                    End::Eof => return Err(invalid(column, &format!("'{{#{}}}' is never closed with '{{/{}}}'", block, block))),
+                }
+                nodes.push(Node::Section { condition, then, otherwise });
+            }
+        }
+    }
+    Ok((nodes, End::Eof))
+}
+
+fn invalid(column: usize, problem: &str) -> KittyError {
+    KittyError::InvalidInput(format!("template: {} (at character {})", problem, column))
+}
+
+fn unknown_placeholder_message(name: &str, dictionaries: &[String]) -> String {
+    let known: Vec<&str> = Placeholder::BUILT_IN
+        .iter()
+        .map(Placeholder::name)
+        .chain(dictionaries.iter().map(String::as_str))
+        .collect();
+    let suggestion = known
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| format!("; did you mean {{{}}}?", candidate))
+        .unwrap_or_default();
+    let known: Vec<String> = known.iter().map(|name| format!("{{{}}}", name)).collect();
+    format!("unknown placeholder {{{}}}{} (known: {})", name, suggestion, known.join(", "))
+}
+
+/// Levenshtein distance between two short strings, for typo suggestions
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders placeholders as `[name]` and picks the configured alternative
+    struct TestContext {
+        choice: usize,
+        energy: u8,
+        state: &'static str,
+        mood: &'static str,
+        age: Option<u8>,
+        known: Vec<Detail>,
+        moment: Moment,
+    }
+
+    impl Default for TestContext {
+        fn default() -> Self {
+            Self {
+                choice: 0,
+                energy: 5,
+                state: "Superposition",
+                mood: "Tranquil",
+                age: None,
+                known: Vec::new(),
+                // Sunday, October 18, 2026, 09:30 UTC
+                moment: Moment::at(1_792_315_800_000_000_000, 0),
+            }
+        }
+    }
+
+    impl RenderContext for TestContext {
+        fn fill(&mut self, placeholder: &Placeholder) -> String {
+            format!("[{}]", placeholder.name())
+        }
+
+        fn choose(&mut self, _options: usize) -> usize {
+            self.choice
+        }
+
+        fn energy_level(&self) -> u8 {
+            self.energy
+        }
+
+        fn quantum_state(&self) -> &str {
+            self.state
+        }
+
+        fn zen_mood(&self) -> &str {
+            self.mood
+        }
+
+        fn age(&self) -> Option<u8> {
+            self.age
+        }
+
+        fn knows(&self, detail: Detail) -> bool {
+            self.known.contains(&detail)
+        }
+
+        fn moment(&self) -> &Moment {
+            &self.moment
+        }
+    }
+
+    fn render(text: &str, ctx: &mut TestContext) -> String {
+        Template::parse(text).unwrap().render(ctx)
+    }
+
+    fn error(text: &str) -> String {
+        match Template::parse(text) {
+            Err(KittyError::InvalidInput(message)) => message,
+            other => panic!("expected InvalidInput for {:?}, got {:?}", text, other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn renders_literals_and_placeholders() {
+        let mut ctx = TestContext::default();
+        assert_eq!(render("The {quantum} field hums. {zen}", &mut ctx), "The [quantum] field hums. [zen]");
+        assert_eq!(render("{ name } and {their} {animal}", &mut ctx), "[name] and [their] [animal]");
+        assert_eq!(render("no slots at all", &mut ctx), "no slots at all");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render("{{kitty}} is {kitty}}}", &mut TestContext::default()), "{kitty} is [kitty]}");
+    }
+
+    #[test]
+    fn substituted_values_are_not_rescanned() {
+        struct Braces;
+        impl RenderContext for Braces {
+            fn fill(&mut self, _: &Placeholder) -> String {
+                "{zen}".to_string()
+            }
+            fn choose(&mut self, _: usize) -> usize {
+                0
+            }
+            fn energy_level(&self) -> u8 {
+                0
+            }
+            fn quantum_state(&self) -> &str {
+                ""
+            }
+            fn zen_mood(&self) -> &str {
+                ""
+            }
+            fn age(&self) -> Option<u8> {
+                None
+            }
+            fn knows(&self, _: Detail) -> bool {
+                false
+            }
+            fn moment(&self) -> &Moment {
+                unreachable!()
+            }
+        }
+        assert_eq!(Template::parse("say {name}").unwrap().render(&mut Braces), "say {zen}");
+    }
+
+    #[test]
+    fn rejects_unbalanced_braces_with_their_position() {
+        assert_eq!(
+            error("ab {kitty"),
+            "template: '{' is never closed (write '{{' for a literal brace) (at character 4)"
+        );
+        assert_eq!(error("a}b"), "template: unmatched '}' (write '}}' for a literal brace) (at character 2)");
+        assert!(error("{kit{ty}").contains("never closed"));
+    }
+
+    #[test]
+    fn suggests_the_closest_placeholder() {
+        let message = error("The {Zen} field");
+        assert!(message.starts_with("template: unknown placeholder {Zen}; did you mean {zen}?"), "{}", message);
+        assert!(message.ends_with("(at character 5)"), "{}", message);
+
+        let message = error("{Nothing Alike}");
+        assert!(!message.contains("did you mean"), "{}", message);
+        assert!(message.contains("(known: {quantum}, {zen}, {name},"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_lowercase_names_are_dictionary_references() {
+        let template = Template::parse("the {qantum} field").unwrap();
+        let Err(KittyError::InvalidInput(message)) = template.check_dictionaries(&[]) else {
+            panic!("{{qantum}} is not a dictionary");
+        };
+        assert!(message.starts_with("template: unknown placeholder {qantum}; did you mean {quantum}?"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_dictionaries_are_rejected_with_suggestions() {
+        let template = Template::parse("a {animal} and a {colour}").unwrap();
+        let known = vec!["animal".to_string(), "color".to_string()];
+
+        let Err(KittyError::InvalidInput(message)) = template.check_dictionaries(&known) else {
+            panic!("{{colour}} is not a dictionary");
+        };
+        assert!(message.starts_with("template: unknown placeholder {colour}; did you mean {color}?"), "{}", message);
+        assert!(message.ends_with("(at character 18)"), "{}", message);
+
+        assert!(Template::parse("a {animal}").unwrap().check_dictionaries(&known).is_ok());
+        assert!(Template::parse("{quantum}").unwrap().check_dictionaries(&[]).is_ok());
+    }
+
+    #[test]
+    fn if_sections_compare_energy_and_age() {
+        let text = "{#if energy>7}bouncy{#else}sleepy{/if}";
+        assert_eq!(render(text, &mut TestContext { energy: 8, ..Default::default() }), "bouncy");
+        assert_eq!(render(text, &mut TestContext { energy: 7, ..Default::default() }), "sleepy");
+
+        let text = "{#if age>=18}toast{#else}juice{/if}";
+        assert_eq!(render(text, &mut TestContext { age: Some(18), ..Default::default() }), "toast");
+        assert_eq!(render(text, &mut TestContext { age: Some(17), ..Default::default() }), "juice");
+        // A comparison never holds while the age is unknown, even a negated one
+        assert_eq!(render(text, &mut TestContext::default()), "juice");
+        assert_eq!(render("{#if age!=3}x{/if}", &mut TestContext::default()), "");
+
+        let mut ctx = TestContext { energy: 5, ..Default::default() };
+        for (condition, holds) in [
+            ("energy<5", false),
+            ("energy<=5", true),
+            ("energy=5", true),
+            ("energy==5", true),
+            ("energy != 5", false),
+            ("energy >= 6", false),
+        ] {
+            let rendered = render(&format!("{{#if {}}}yes{{/if}}", condition), &mut ctx);
+            assert_eq!(rendered == "yes", holds, "{}", condition);
+        }
+    }
+
+    #[test]
+    fn if_sections_test_for_supplied_details() {
+        let text = "{#if sender}from {sender}{#else}anonymous{/if}";
+        assert_eq!(render(text, &mut TestContext::default()), "anonymous");
+        let mut ctx = TestContext { known: vec![Detail::Sender], ..Default::default() };
+        assert_eq!(render(text, &mut ctx), "from [sender]");
+        assert_eq!(render("{#if recipient}to {name}{/if}", &mut ctx), "");
+    }
+
+    #[test]
+    fn mood_and_state_sections_match_any_listed_name() {
+        let text = "{#mood Playful|Tranquil}calm{#else}restless{/mood}";
+        assert_eq!(render(text, &mut TestContext { mood: "Tranquil", ..Default::default() }), "calm");
+        assert_eq!(render(text, &mut TestContext { mood: "Curious", ..Default::default() }), "restless");
+
+        let text = "{#state Entangled}linked{/state}";
+        assert_eq!(render(text, &mut TestContext { state: "Entangled", ..Default::default() }), "linked");
+        assert_eq!(render(text, &mut TestContext::default()), "");
+
+        let text = "{#if mood!=Tranquil}awake{#else}asleep{/if} {#if state=Superposition|Entangled}both{/if}";
+        assert_eq!(render(text, &mut TestContext::default()), "asleep both");
+    }
+
+    #[test]
+    fn calendar_conditions_match_the_local_moment() {
+        // The default moment is a Sunday morning in autumn
+        let mut ctx = TestContext::default();
+        assert_eq!(render("{#if weekday=sunday}lazy{/if}", &mut ctx), "lazy");
+        assert_eq!(render("{#if season=Winter|Spring}cold{#else}mild{/if}", &mut ctx), "mild");
+        assert_eq!(render("{#if time_of_day!=night}awake{/if}", &mut ctx), "awake");
+        assert!(error("{#if weekday=Caturday}x{/if}").contains("'Caturday' is not a weekday (expected one of: Monday,"));
+    }
+
+    #[test]
+    fn sections_nest() {
+        let text = "{#if energy>3}{#mood Playful}zoom{#else}trot{/mood}{#else}nap{/if}";
+        assert_eq!(render(text, &mut TestContext { mood: "Playful", ..Default::default() }), "zoom");
+        assert_eq!(render(text, &mut TestContext::default()), "trot");
+        assert_eq!(render(text, &mut TestContext { energy: 2, ..Default::default() }), "nap");
+    }
+
+    #[test]
+    fn alternatives_render_the_chosen_option() {
+        let text = "{purr|mew|chirp}!";
+        assert_eq!(render(text, &mut TestContext::default()), "purr!");
+        assert_eq!(render(text, &mut TestContext { choice: 2, ..Default::default() }), "chirp!");
+        // An out-of-range choice falls back to the last option
+        assert_eq!(render(text, &mut TestContext { choice: 9, ..Default::default() }), "chirp!");
+        assert_eq!(render("{|yes}", &mut TestContext::default()), "");
+    }
+
+    #[test]
+    fn referenced_names_include_negated_and_nested_conditions() {
+        let template = Template::parse("{#if mood!=Playful}{#state Entangled|Collapsed}x{/state}{/if}{#mood Tranquil}y{/mood}")
+            .unwrap();
+        assert_eq!(template.referenced_moods(), ["Playful", "Tranquil"]);
+        assert_eq!(template.referenced_states(), ["Entangled", "Collapsed"]);
+    }
+
+    #[test]
+    fn rejects_malformed_sections() {
+        assert_eq!(error("a {#if energy>7}x"), "template: '{#if}' is never closed with '{/if}' (at character 3)");
+        assert_eq!(error("x{/mood}"), "template: '{/mood}' without a matching opening tag (at character 2)");
+        assert_eq!(error("{#else}"), "template: '{#else}' outside a section (at character 1)");
+        assert!(error("{#if energy>7}x{/mood}").contains("'{/mood}' closes a '{#if}' section"));
+        assert!(error("{#mood Playful}a{#else}b{#else}c{/mood}").contains("only have one '{#else}'"));
+        assert!(error("{#unless energy>7}x{/unless}").contains("unknown section '{#unless energy>7}'"));
+        assert!(error("{#if energy>high}x{/if}").contains("'high' is not a valid energy (expected a number)"));
+        assert!(error("{#if energy>300}x{/if}").contains("'300' is not a valid energy"));
+        assert!(error("{#if mood>Playful}x{/if}").contains("mood can only be compared with '=' or '!='"));
+        assert!(error("{#if colour=red}x{/if}").contains("unknown condition subject 'colour'"));
+        assert!(error("{#if}x{/if}").contains("'{#if}' needs a condition"));
+        assert!(error("{#mood Playful|}x{/mood}").contains("'mood' condition needs one or more names"));
+    }
+
+    #[test]
+    fn lenient_parse_keeps_invalid_text_literal() {
+        let template = Template::parse_lenient("legacy {unclosed");
+        assert_eq!(template.render(&mut TestContext::default()), "legacy {unclosed");
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "zen"), 3);
+        assert_eq!(edit_distance("quantum", "quantum"), 0);
+        assert_eq!(edit_distance("qantum", "quantum"), 1);
+    }
+
+    #[test]
+    fn ordinals_use_the_english_suffixes() {
+        let ordinals: Vec<String> = [1, 2, 3, 4, 11, 12, 13, 21, 22, 23, 101, 111, 112].into_iter().map(ordinal).collect();
+        assert_eq!(
+            ordinals,
+            ["1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd", "23rd", "101st", "111th", "112th"]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+// Input validation and sanitization at the API boundary
+//
+// Every piece of user-supplied text goes through `sanitize_text` before it is
+// stored or echoed back:
+//   1. oversized raw input is rejected before any work is done on it
+//   2. Unicode is normalized to NFC so look-alike strings compare equal
+//   3. control and invisible formatting characters (zero-width, bidi overrides) are stripped,
+//      line breaks and tabs become spaces, and runs of whitespace collapse to one space
+//   4. empty results and results over the length limit are rejected
+//
+// Cleaned text is stored and returned as typed, without HTML escaping: error messages
+// quote input and templates compare with '<' and '>', so no escaping done here could
+// cover every path. Frontends escape when they render (the bundled one assigns backend
+// text with `textContent` or escapes it before building markup).
+use unicode_normalization::UnicodeNormalization;
+
+use crate::error::KittyError;
+
+/// Longest allowed user or kitty name, in characters
+pub const MAX_NAME_CHARS: usize = 64;
+
+/// Longest allowed context, state or mood key, in characters
+pub const MAX_KEY_CHARS: usize = 32;
+
+/// Longest allowed wisdom template, in characters
+pub const MAX_TEMPLATE_CHARS: usize = 500;
+
+/// Longest allowed zen phrase, in characters
+pub const MAX_PHRASE_CHARS: usize = 200;
+
+/// Longest allowed quantum adjective, in characters
+pub const MAX_ADJECTIVE_CHARS: usize = 40;
+
+/// Raw input longer than this multiple of the limit (in bytes) is rejected before normalization
+const RAW_BYTES_PER_CHAR: usize = 4;
+
+/// Normalize and clean `input`, rejecting it if it is empty or too long
+/// `field` names the argument in error messages.
+pub fn sanitize_text(field: &str, input: &str, max_chars: usize) -> Result<String, KittyError> {
+    if input.len() > max_chars * RAW_BYTES_PER_CHAR {
+        return Err(too_long(field, max_chars));
+    }
+
+    let mut cleaned = String::with_capacity(input.len());
+    let mut pending_space = false;
+    for c in input.nfc() {
+        if c.is_whitespace() {
+            pending_space = true;
+        } else if c.is_control() || is_invisible_format(c) {
+            continue;
+        } else {
+            if pending_space && !cleaned.is_empty() {
+                cleaned.push(' ');
+            }
+            pending_space = false;
+            cleaned.push(c);
+        }
+    }
+
+    if cleaned.is_empty() {
+        return Err(KittyError::InvalidInput(format!("{} must not be empty", field)));
+    }
+    if cleaned.chars().count() > max_chars {
+        return Err(too_long(field, max_chars));
+    }
+
+    Ok(cleaned)
+}
+
+/// Validate a user or kitty name
+pub fn name(field: &str, input: &str) -> Result<String, KittyError> {
+    sanitize_text(field, input, MAX_NAME_CHARS)
+}
+
+/// Validate a wisdom template
+pub fn template(input: &str) -> Result<String, KittyError> {
+    sanitize_text("template", input, MAX_TEMPLATE_CHARS)
+}
+
+/// Validate a quantum state or zen mood name (matched against the configured catalogue)
+pub fn catalogue_key(field: &str, input: &str) -> Result<String, KittyError> {
+    sanitize_text(field, input, MAX_KEY_CHARS)
+}
+
+/// Validate a context key: lowercased, limited to ASCII letters, digits, '-' and '_'
+pub fn context_key(input: &str) -> Result<String, KittyError> {
+    slug("context", input)
+}
+
+/// Validate a slot dictionary name (same rules as a context key)
+pub fn dictionary_name(input: &str) -> Result<String, KittyError> {
+    slug("dictionary", input)
+}
+
+/// Validate a template tag (same rules as a context key)
+pub fn tag(input: &str) -> Result<String, KittyError> {
+    slug("tag", input)
+}
+
+/// Validate a wisdom request keyword (same rules as a template tag, which it is matched against)
+pub fn keyword(input: &str) -> Result<String, KittyError> {
+    slug("keyword", input)
+}
+
+fn slug(field: &str, input: &str) -> Result<String, KittyError> {
+    let key = sanitize_text(field, input, MAX_KEY_CHARS)?.to_lowercase();
+    if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(KittyError::InvalidInput(format!(
+            "{} may only contain letters, digits, '-' and '_'",
+            field
+        )));
+    }
+    Ok(key)
+}
+
+/// Zero-width and bidirectional formatting characters that render invisibly
+fn is_invisible_format(c: char) -> bool {
+    matches!(c,
+        '\u{00AD}'                  // soft hyphen
+        | '\u{200B}'                // zero-width space (joiners are kept for emoji sequences)
+        | '\u{200E}' | '\u{200F}'   // left-to-right and right-to-left marks
+        | '\u{202A}'..='\u{202E}'   // bidi embeddings and overrides
+        | '\u{2060}'..='\u{2064}'   // word joiner, invisible operators
+        | '\u{2066}'..='\u{2069}'   // bidi isolates
+        | '\u{FEFF}'                // byte order mark
+    )
+}
+
+fn too_long(field: &str, max_chars: usize) -> KittyError {
+    KittyError::InvalidInput(format!("{} must be at most {} characters", field, max_chars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_normalizes_and_strips_invisible_characters() {
+        assert_eq!(sanitize_text("name", "  O'Brien\u{200B}\t\n<3 ", MAX_NAME_CHARS).unwrap(), "O'Brien <3");
+        assert_eq!(sanitize_text("name", "Cafe\u{301}", MAX_NAME_CHARS).unwrap(), "Caf\u{e9}");
+        assert!(sanitize_text("name", " \u{200B}\u{202E} ", MAX_NAME_CHARS).is_err());
+    }
+
+    #[test]
+    fn sanitize_enforces_the_length_limit() {
+        assert!(sanitize_text("name", &"&".repeat(MAX_NAME_CHARS), MAX_NAME_CHARS).is_ok());
+        assert!(sanitize_text("name", &"a".repeat(MAX_NAME_CHARS + 1), MAX_NAME_CHARS).is_err());
+        assert!(sanitize_text("name", &" ".repeat(MAX_NAME_CHARS * RAW_BYTES_PER_CHAR + 1), MAX_NAME_CHARS).is_err());
+    }
+
+    #[test]
+    fn text_is_stored_as_typed() {
+        assert_eq!(
+            template("{#if energy>7}Kitty's <zoomies>{/if} {{a & b}}").unwrap(),
+            "{#if energy>7}Kitty's <zoomies>{/if} {{a & b}}"
+        );
+        assert_eq!(catalogue_key("state", " <img src=x> ").unwrap(), "<img src=x>");
+    }
+
+    #[test]
+    fn slugs_are_lowercased_and_restricted() {
+        assert_eq!(context_key(" Birthday_2 ").unwrap(), "birthday_2");
+        assert!(tag("two words").is_err());
+        assert!(keyword("<b>").is_err());
+    }
+}
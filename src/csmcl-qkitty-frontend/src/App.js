@@ -40,7 +40,11 @@ class App {
       // Call the quantum_greet function from the backend canister
       // This uses the auto-generated bindings from 'declarations/csmcl-qkitty-backend'
       // The call is asynchronous and returns a Promise
-      const response = await csmcl_qkitty_backend.quantum_greet(name);
+      const result = await csmcl_qkitty_backend.quantum_greet(name);
+      if ('Err' in result) {
+        throw new Error(Object.values(result.Err)[0]);
+      }
+      const response = result.Ok;
       
       // Update the component state with the response data
       this.greeting = response.greeting;
@@ -1,10 +1,40 @@
 // Import the auto-generated JavaScript bindings for our backend canister
-import { csmcl_qkitty_backend } from 'declarations/csmcl-qkitty-backend';
+import { csmcl_qkitty_backend, createActor, canisterId } from 'declarations/csmcl-qkitty-backend';
 
 // Import Internet Identity related modules
 import { AuthClient } from '@dfinity/auth-client';
 import { HttpAgent } from '@dfinity/agent';
 
+/**
+ * Unwrap a Candid `variant { Ok; Err: KittyError }` result
+ * Throws an Error carrying the backend's explanation when the call was refused
+ * @param {object} result - The result returned by the backend
+ */
+function unwrapResult(result) {
+  if ('Err' in result) {
+    const [kind, message] = Object.entries(result.Err)[0];
+    const error = new Error(message);
+    error.kind = kind;
+    throw error;
+  }
+  return result.Ok;
+}
+
+/**
+ * Escape text for interpolation into HTML markup
+ * Backend text is stored as typed, so it must be escaped wherever it's built into HTML
+ * (or assigned with `textContent` instead)
+ * @param {string} text - The text to escape
+ */
+function escapeHtml(text) {
+  return String(text)
+    .replace(/&/g, '&amp;')
+    .replace(/</g, '&lt;')
+    .replace(/>/g, '&gt;')
+    .replace(/"/g, '&quot;')
+    .replace(/'/g, '&#39;');
+}
+
 /**
  * Quantum Kitty Controller
  * Handles interactions with the ICP backend canister
@@ -24,6 +54,9 @@ class QuantumKittyController {
     this.principal = null;
     this.isAuthenticated = false;
     
+    // Backend actor; anonymous until the user logs in, so per-user calls are keyed on their principal
+    this.backend = csmcl_qkitty_backend;
+    
     // Initialize auth client and event listeners
     this.initAuth();
     this.setupEventListeners();
@@ -47,6 +80,7 @@ class QuantumKittyController {
         this.identity = await this.authClient.getIdentity();
         this.principal = this.identity.getPrincipal().toString();
         this.isAuthenticated = true;
+        this.backend = createActor(canisterId, { agentOptions: { identity: this.identity } });
         this.updateAuthUI();
         
         // Load user's kitty data
@@ -76,6 +110,7 @@ class QuantumKittyController {
           this.identity = await this.authClient.getIdentity();
           this.principal = this.identity.getPrincipal().toString();
           this.isAuthenticated = true;
+          this.backend = createActor(canisterId, { agentOptions: { identity: this.identity } });
           this.updateAuthUI();
           
           // Check if user has a kitty already
@@ -97,6 +132,7 @@ class QuantumKittyController {
       this.identity = null;
       this.principal = null;
       this.isAuthenticated = false;
+      this.backend = csmcl_qkitty_backend;
       this.kittyName = '';
       this.updateAuthUI();
       
@@ -159,7 +195,7 @@ class QuantumKittyController {
   async loadKittyData() {
     try {
       // Call the backend to check if user has a bonded kitty
-      const savedKittyName = await csmcl_qkitty_backend.get_kitty_name();
+      const savedKittyName = unwrapResult(await this.backend.get_kitty_name());
       
       if (savedKittyName && savedKittyName.length > 0) {
         // User has a bonded kitty
@@ -167,7 +203,7 @@ class QuantumKittyController {
         console.log(`Dimensional bond detected with ${this.kittyName}`);
         
         // Get the current kitty state
-        const state = await csmcl_qkitty_backend.update_kitty_state();
+        const state = await this.backend.get_kitty_state();
         this.currentState = state.quantum_state;
         this.currentEnergyLevel = state.energy_level;
         this.currentZenMood = state.zen_mood;
@@ -201,16 +237,16 @@ class QuantumKittyController {
       this.showLoading('Creating quantum bond with your kitty...');
       
       // Save kitty name to backend to create a persistent dimensional bond
-      await csmcl_qkitty_backend.save_kitty_name(name);
+      unwrapResult(await this.backend.save_kitty_name(name));
       
       // Get the current kitty state
-      const state = await csmcl_qkitty_backend.update_kitty_state();
+      const state = await this.backend.get_kitty_state();
       this.currentState = state.quantum_state;
       this.currentEnergyLevel = state.energy_level;
       this.currentZenMood = state.zen_mood;
       
       // Generate a wisdom response for the new bond
-      const wisdom = await csmcl_qkitty_backend.generate_kitty_wisdom(name, ['bonding', 'general']);
+      const wisdom = unwrapResult(await this.backend.generate_kitty_wisdom(name, ['bonding', 'general']));
       
       // If the backend doesn't have bonding templates, create a special message
       if (wisdom.content.includes('meditating deeply')) {
@@ -250,7 +286,7 @@ class QuantumKittyController {
       
       // Generate a special reunion wisdom message
       const reunionContext = 'reunion';
-      const wisdom = await csmcl_qkitty_backend.generate_kitty_wisdom(this.kittyName, [reunionContext, 'general']);
+      const wisdom = unwrapResult(await this.backend.generate_kitty_wisdom(this.kittyName, [reunionContext, 'general']));
       
       // Update the greeting section with a special reunion message
       const greetingElement = document.getElementById('greeting');
@@ -303,12 +339,12 @@ class QuantumKittyController {
         <div class="quantum-state-container">
           <div class="quantum-state">
             <h3>Quantum State</h3>
-            <div class="state-badge ${this.currentState.toLowerCase()}">${this.currentState}</div>
+            <div class="state-badge ${escapeHtml(this.currentState.toLowerCase())}">${escapeHtml(this.currentState)}</div>
           </div>
           
           <div class="zen-mood">
             <h3>Zen Mood</h3>
-            <div class="mood-badge ${this.currentZenMood.toLowerCase()}">${this.currentZenMood}</div>
+            <div class="mood-badge ${escapeHtml(this.currentZenMood.toLowerCase())}">${escapeHtml(this.currentZenMood)}</div>
           </div>
           
           <div class="energy-level">
@@ -321,8 +357,8 @@ class QuantumKittyController {
         </div>
         
         <div class="kitty-actions">
-          <button id="pet-kitty-button" class="quantum-button">Pet ${this.kittyName}</button>
-          <button id="feed-kitty-button" class="quantum-button">Feed ${this.kittyName}</button>
+          <button id="pet-kitty-button" class="quantum-button">Pet ${escapeHtml(this.kittyName)}</button>
+          <button id="feed-kitty-button" class="quantum-button">Feed ${escapeHtml(this.kittyName)}</button>
           <button id="request-wisdom-button" class="quantum-button">Request Quantum Wisdom</button>
         </div>
       </div>
@@ -459,7 +495,7 @@ class QuantumKittyController {
       this.showLoading('Quantum calculation in progress...');
       
       // Call the backend canister
-      const response = await csmcl_qkitty_backend.quantum_greet(name);
+      const response = unwrapResult(await this.backend.quantum_greet(name));
       
       // Update the UI with the response
       this.updateQuantumResponse(response);
@@ -480,7 +516,7 @@ class QuantumKittyController {
       
       // For now, we'll just call quantum_greet since we don't have a pet_kitty function yet
       // In the future, you can implement a pet_kitty function in the backend
-      const response = await csmcl_qkitty_backend.quantum_greet(this.currentName || 'Kitty Petter');
+      const response = unwrapResult(await this.backend.quantum_greet(this.currentName || 'Kitty Petter'));
       
       // Modify the response to make it pet-specific
       if (kittyName) {
@@ -516,7 +552,7 @@ class QuantumKittyController {
       
       // For now, we'll just call quantum_greet since we don't have a feed_kitty function yet
       // In the future, you can implement a feed_kitty function in the backend
-      const response = await csmcl_qkitty_backend.quantum_greet(this.currentName || 'Kitty Feeder');
+      const response = unwrapResult(await this.backend.quantum_greet(this.currentName || 'Kitty Feeder'));
       
       // Modify the response to make it food-specific
       if (kittyName) {
@@ -549,7 +585,7 @@ class QuantumKittyController {
   showLoading(message) {
     const detailsElement = document.getElementById('quantum-details');
     if (detailsElement) {
-      detailsElement.innerHTML = `<div class="loading">${message}</div>`;
+      detailsElement.innerHTML = `<div class="loading">${escapeHtml(message)}</div>`;
     }
   }
   
@@ -560,7 +596,7 @@ class QuantumKittyController {
   showError(message) {
     const detailsElement = document.getElementById('quantum-details');
     if (detailsElement) {
-      detailsElement.innerHTML = `<div class="error">${message}</div>`;
+      detailsElement.innerHTML = `<div class="error">${escapeHtml(message)}</div>`;
     }
     
     // Also update the greeting to show error state
@@ -572,12 +608,12 @@ class QuantumKittyController {
   }
 
   /**
-   * Update the global state from the backend
+   * Refresh the global state from the backend
    */
   async updateGlobalState() {
     try {
-      // Call the backend to update the global kitty state
-      const state = await csmcl_qkitty_backend.update_kitty_state();
+      // Read the global kitty state; the backend's pulse timer keeps it current
+      const state = await this.backend.get_kitty_state();
       
       // Store the state for future interactions
       this.currentState = state.quantum_state;
@@ -605,7 +641,7 @@ class QuantumKittyController {
       this.showLoading('Quantum wisdom materializing...');
       
       // Call the backend to generate wisdom
-      const wisdom = await csmcl_qkitty_backend.generate_kitty_wisdom(this.kittyName, [context]);
+      const wisdom = unwrapResult(await this.backend.generate_kitty_wisdom(this.kittyName, [context]));
       
       // Save state for future interactions
       this.currentState = wisdom.quantum_state;
@@ -626,19 +662,19 @@ class QuantumKittyController {
       const html = `
         <div class="quantum-details">
           <div class="wisdom-container">
-            <div class="wisdom-content">${wisdom.content}</div>
-            <div class="wisdom-context">${context}</div>
+            <div class="wisdom-content">${escapeHtml(wisdom.content)}</div>
+            <div class="wisdom-context">${escapeHtml(context)}</div>
           </div>
           
           <div class="quantum-state-container">
             <div class="quantum-state">
               <h3>Quantum State</h3>
-              <div class="state-badge ${wisdom.quantum_state.toLowerCase()}">${wisdom.quantum_state}</div>
+              <div class="state-badge ${escapeHtml(wisdom.quantum_state.toLowerCase())}">${escapeHtml(wisdom.quantum_state)}</div>
             </div>
             
             <div class="zen-mood">
               <h3>Zen Mood</h3>
-              <div class="mood-badge ${wisdom.zen_mood.toLowerCase()}">${wisdom.zen_mood}</div>
+              <div class="mood-badge ${escapeHtml(wisdom.zen_mood.toLowerCase())}">${escapeHtml(wisdom.zen_mood)}</div>
             </div>
             
             <div class="energy-level">
@@ -703,13 +739,13 @@ class QuantumKittyController {
       <div class="quantum-details">
         <div class="quantum-state">
           <h3>Quantum State</h3>
-          <div class="state-badge ${response.quantum_state.toLowerCase()}">${response.quantum_state}</div>
+          <div class="state-badge ${escapeHtml(response.quantum_state.toLowerCase())}">${escapeHtml(response.quantum_state)}</div>
         </div>
         
         ${response.zen_mood ? `
         <div class="zen-mood">
           <h3>Zen Mood</h3>
-          <div class="mood-badge ${response.zen_mood.toLowerCase()}">${response.zen_mood}</div>
+          <div class="mood-badge ${escapeHtml(response.zen_mood.toLowerCase())}">${escapeHtml(response.zen_mood)}</div>
         </div>` : ''}
         
         <div class="energy-level">